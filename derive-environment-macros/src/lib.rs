@@ -1,36 +1,622 @@
 #![doc = include_str!("../README.md")]
 
 use convert_case::{Case, Casing};
-use darling::{ast, FromDeriveInput, FromField};
+use darling::{
+	ast,
+	usage::{GenericsExt, Purpose, UsesTypeParams},
+	FromDeriveInput, FromField, FromMeta, FromVariant,
+};
 use proc_macro2::TokenStream;
-use quote::quote;
-use syn::*;
+use quote::{format_ident, quote, ToTokens};
+use syn::{ext::IdentExt, *};
 
 #[derive(Debug, FromDeriveInput)]
-#[darling(attributes(env), supports(struct_named))]
+#[darling(
+	attributes(env),
+	supports(
+		struct_named,
+		struct_newtype,
+		struct_tuple,
+		enum_named,
+		enum_newtype,
+		enum_tuple,
+		enum_unit
+	),
+	and_then = EnvArgs::validate
+)]
 #[allow(dead_code)]
 struct EnvArgs {
 	ident: syn::Ident,
 	generics: syn::Generics,
-	data: ast::Data<(), EnvFieldArgs>,
+	data: ast::Data<EnvVariantArgs, EnvFieldArgs>,
+
+	/// Name of the variable which selects an enum's variant.
+	#[darling(default)]
+	tag: Option<String>,
+	/// Prefix used by `FromEnv::from_env`.
+	#[darling(default)]
+	prefix: Option<String>,
+	/// Placed between the prefix and each field's name, and inherited by nested types.
+	#[darling(default)]
+	separator: Option<String>,
+	/// Case that field names are converted to.
+	#[darling(default)]
+	case: Option<CaseStyle>,
+	/// Treats every field as `required`.
+	#[darling(default)]
+	deny_missing: bool,
+	/// Treats every field as `file`.
+	#[darling(default)]
+	file: bool,
+	/// Implements `Default` using each field's default.
+	#[darling(default)]
+	default: bool,
+	/// Implements `EnvSchema` unless set to `false`.
+	#[darling(default)]
+	schema: Option<bool>,
+	/// Replaces the inferred bounds of the `FromEnv` and `EnvSchema` implementations.
+	#[darling(default, with = parse_bound)]
+	bound: Option<Vec<WherePredicate>>,
 }
 
-#[derive(Debug, FromField)]
+/// Parses `#[env(bound = "...")]` as the contents of a where clause.
+fn parse_bound(meta: &Meta) -> darling::Result<Option<Vec<WherePredicate>>> {
+	let bound = String::from_meta(meta)?;
+	let clause: WhereClause = syn::parse_str(&format!("where {bound}"))
+		.map_err(|error| darling::Error::custom(error).with_span(meta))?;
+	Ok(Some(clause.predicates.into_iter().collect()))
+}
+
+impl EnvArgs {
+	/// Returns every field of the struct, or of each of the enum's variants.
+	fn all_fields(&self) -> Vec<&EnvFieldArgs> {
+		match &self.data {
+			ast::Data::Struct(fields) => fields.iter().collect(),
+			ast::Data::Enum(variants) => variants.iter().flat_map(|v| v.fields.iter()).collect(),
+		}
+	}
+
+	/// Returns every field matching `filter` whose type uses one of the type parameters.
+	fn generic_fields(&self, filter: impl Fn(&EnvFieldArgs) -> bool) -> Vec<&EnvFieldArgs> {
+		let declared = self.generics.declared_type_params();
+		let options = Purpose::BoundImpl.into();
+		self.all_fields()
+			.into_iter()
+			.filter(|field| filter(field))
+			.filter(|field| !field.ty.uses_type_params(&options, &declared).is_empty())
+			.collect()
+	}
+
+	/// Bounds the type of each field matching `filter` which uses a type parameter with `bound`,
+	/// such as `Vec<T>: FromEnv` rather than `T: FromEnv`.
+	fn bounds(
+		&self,
+		filter: impl Fn(&EnvFieldArgs) -> bool,
+		bound: TokenStream,
+	) -> Vec<WherePredicate> {
+		self.generic_fields(filter)
+			.into_iter()
+			.map(|field| {
+				let ty = &field.ty;
+				parse_quote!(#ty: #bound)
+			})
+			.collect()
+	}
+
+	/// Clones the generics, adding `predicates` to the where clause.
+	fn generics_with(&self, predicates: Vec<WherePredicate>) -> Generics {
+		let mut generics = self.generics.clone();
+		let where_clause = generics.make_where_clause();
+		for predicate in predicates {
+			// Fields of the same type would otherwise repeat their bounds.
+			let text = predicate.to_token_stream().to_string();
+			if !where_clause
+				.predicates
+				.iter()
+				.any(|existing| existing.to_token_stream().to_string() == text)
+			{
+				where_clause.predicates.push(predicate);
+			}
+		}
+		generics
+	}
+
+	/// Generics of the `FromEnv` implementation.
+	fn env_generics(&self) -> Generics {
+		if let Some(bound) = &self.bound {
+			return self.generics_with(bound.clone());
+		}
+
+		let mut predicates = Vec::new();
+		for field in self.generic_fields(|field| !field.ignore && field.parser().is_none()) {
+			predicates.extend(field.load_bounds());
+		}
+		// Switching an enum's variant builds each of the new variant's fields using `Default`.
+		if self.data.is_enum() {
+			predicates.extend(self.bounds(|_| true, quote!(::std::default::Default)));
+		}
+		self.generics_with(predicates)
+	}
+
+	/// Generics of the `EnvSchema` implementation.
+	fn schema_generics(&self) -> Generics {
+		if let Some(bound) = &self.bound {
+			return self.generics_with(bound.clone());
+		}
+
+		self.generics_with(self.bounds(
+			|field| !field.ignore && !field.skip_schema && field.parser().is_none(),
+			quote!(::derive_environment::EnvSchema),
+		))
+	}
+
+	/// Generics of the `Default` implementation.
+	fn default_generics(&self) -> Generics {
+		let mut predicates = self.bounds(
+			|field| !field.has_default(),
+			quote!(::std::default::Default),
+		);
+		// Defaults of vectors and maps are loaded into the type's own default value, like their variables.
+		// Other defaults are parsed using `FromStr`, which type parameters can't be known to implement,
+		// so they fall back to loading the default value too.
+		for field in
+			self.generic_fields(|field| field.default.is_some() && field.parser().is_none())
+		{
+			let ty = &field.ty;
+			predicates.push(parse_quote!(#ty: ::std::default::Default));
+			if field.vec_options().is_some() || field.map_options().is_some() {
+				predicates.extend(field.load_bounds());
+			} else {
+				predicates.push(parse_quote!(#ty: ::derive_environment::FromEnv));
+			}
+		}
+		self.generics_with(predicates)
+	}
+
+	fn validate(self) -> darling::Result<Self> {
+		let mut errors = darling::Error::accumulator();
+
+		match &self.data {
+			ast::Data::Struct(fields) if !self.default => {
+				for field in fields.iter().filter(|field| field.has_default()) {
+					errors.push(
+						darling::Error::custom(
+							"field defaults require `#[env(default)]` on the container",
+						)
+						.with_span(&field.ty),
+					);
+				}
+			}
+			ast::Data::Struct(_) => {}
+			ast::Data::Enum(_) if self.default => {
+				errors.push(
+					darling::Error::custom("`#[env(default)]` is only supported on structs")
+						.with_span(&self.ident),
+				);
+			}
+			ast::Data::Enum(variants) => {
+				for field in variants
+					.iter()
+					.flat_map(|variant| variant.fields.iter())
+					.filter(|field| field.has_default())
+				{
+					errors.push(
+						darling::Error::custom("field defaults are only supported in structs")
+							.with_span(&field.ty),
+					);
+				}
+			}
+		}
+
+		errors.finish_with(self)
+	}
+
+	/// Sets the container's separator for the rest of the generated function, if it has one.
+	///
+	/// Nested types without a separator of their own inherit it, as do vector indices and map keys.
+	fn set_separator(&self) -> TokenStream {
+		self.separator.as_ref().map_or_else(
+			TokenStream::new,
+			|separator| quote!(let _separator = ::derive_environment::__private::set_separator(#separator);),
+		)
+	}
+
+	fn case(&self) -> Case {
+		self.case.map_or(Case::UpperSnake, |case| case.0)
+	}
+
+	fn tag(&self) -> String {
+		match &self.tag {
+			Some(tag) => tag.clone(),
+			None => "kind".to_case(self.case()),
+		}
+	}
+}
+
+/// A case style accepted by `#[env(case = "...")]`.
+#[derive(Clone, Copy, Debug)]
+struct CaseStyle(Case);
+
+impl FromMeta for CaseStyle {
+	fn from_string(value: &str) -> darling::Result<Self> {
+		match value {
+			"SCREAMING_SNAKE" | "UPPER_SNAKE" => Ok(Self(Case::UpperSnake)),
+			"lower" | "snake" => Ok(Self(Case::Snake)),
+			"kebab" => Ok(Self(Case::Kebab)),
+			"SCREAMING-KEBAB" | "UPPER-KEBAB" => Ok(Self(Case::UpperKebab)),
+			_ => Err(darling::Error::unknown_value(value)),
+		}
+	}
+}
+
+#[derive(Debug, FromVariant)]
 #[darling(attributes(env))]
 #[allow(dead_code)]
+struct EnvVariantArgs {
+	ident: syn::Ident,
+	fields: ast::Fields<EnvFieldArgs>,
+
+	#[darling(default)]
+	rename: Option<String>,
+}
+
+#[derive(Debug, FromField)]
+#[darling(attributes(env), forward_attrs(doc), and_then = EnvFieldArgs::validate)]
+#[allow(dead_code)]
 struct EnvFieldArgs {
 	ident: Option<syn::Ident>,
 	ty: syn::Type,
+	attrs: Vec<Attribute>,
 
 	#[darling(default)]
 	ignore: bool,
+	/// Replaces the field's segment of the variable name.
+	#[darling(default)]
+	rename: Option<String>,
+	/// Additional segments to try, in order, if the variable is absent.
+	#[darling(multiple)]
+	alias: Vec<String>,
+	/// Reads the field's variables directly from the prefix, without adding a segment.
+	#[darling(default)]
+	flatten: bool,
+	/// Fails if none of the field's variables were found.
+	#[darling(default)]
+	required: bool,
+	/// Parsed using `FromStr`, or read like the field's variable, to produce the field's default value.
+	#[darling(default)]
+	default: Option<String>,
+	/// Called to produce the field's default value.
+	#[darling(default)]
+	default_with: Option<syn::Path>,
+	/// Parses the field's variable using a `fn(&str) -> Result<T, E>` instead of `FromEnv`.
+	#[darling(default)]
+	parse_with: Option<syn::Path>,
+	/// Parses the field as a boolean which is `true` when its variable is set but empty.
+	#[darling(default)]
+	flag: bool,
+	/// Removes the variable's value from any errors thrown while loading the field.
+	#[darling(default)]
+	redact: bool,
+	/// Leaves the field's variables out of the `EnvSchema` implementation.
+	#[darling(default)]
+	skip_schema: bool,
+	/// Reads the variable from the file named by `{VAR}_FILE` if the variable itself is absent.
+	#[darling(default)]
+	file: bool,
+	/// A module whose `parse` function is used like `parse_with`.
+	#[darling(default)]
+	with: Option<syn::Path>,
+	/// Reads a `Vec` from a single variable separated by this character, before trying indexed variables.
+	#[darling(default)]
+	delimiter: Option<char>,
+	/// How a `Vec`'s indexed variables are found.
+	#[darling(default)]
+	indices: Option<IndicesStyle>,
+	/// How a `Vec`'s loaded elements are combined with its existing elements.
+	#[darling(default)]
+	vec_mode: Option<VecModeStyle>,
+	/// How a map's keys are converted before they are parsed.
+	#[darling(default)]
+	key_case: Option<KeyCaseStyle>,
+}
+
+/// A `derive_environment::vec::Indices` accepted by `#[env(indices = "...")]`.
+#[derive(Clone, Copy, Debug, FromMeta)]
+#[darling(rename_all = "snake_case")]
+enum IndicesStyle {
+	Contiguous,
+	Compact,
+	Strict,
+}
+
+impl ToTokens for IndicesStyle {
+	fn to_tokens(&self, tokens: &mut TokenStream) {
+		let variant = match self {
+			IndicesStyle::Contiguous => quote!(Contiguous),
+			IndicesStyle::Compact => quote!(Compact),
+			IndicesStyle::Strict => quote!(Strict),
+		};
+		tokens.extend(quote!(::derive_environment::vec::Indices::#variant));
+	}
+}
+
+/// A `derive_environment::vec::VecMode` accepted by `#[env(vec_mode = "...")]`.
+#[derive(Clone, Copy, Debug, FromMeta)]
+#[darling(rename_all = "snake_case")]
+enum VecModeStyle {
+	Replace,
+	Merge,
+	Append,
+}
+
+impl ToTokens for VecModeStyle {
+	fn to_tokens(&self, tokens: &mut TokenStream) {
+		let variant = match self {
+			VecModeStyle::Replace => quote!(Replace),
+			VecModeStyle::Merge => quote!(Merge),
+			VecModeStyle::Append => quote!(Append),
+		};
+		tokens.extend(quote!(::derive_environment::vec::VecMode::#variant));
+	}
+}
+
+/// A `derive_environment::map::KeyCase` accepted by `#[env(key_case = "...")]`.
+#[derive(Clone, Copy, Debug, FromMeta)]
+#[darling(rename_all = "snake_case")]
+enum KeyCaseStyle {
+	Preserve,
+	Lower,
+	Upper,
+	Kebab,
+}
+
+impl ToTokens for KeyCaseStyle {
+	fn to_tokens(&self, tokens: &mut TokenStream) {
+		let variant = match self {
+			KeyCaseStyle::Preserve => quote!(Preserve),
+			KeyCaseStyle::Lower => quote!(Lower),
+			KeyCaseStyle::Upper => quote!(Upper),
+			KeyCaseStyle::Kebab => quote!(Kebab),
+		};
+		tokens.extend(quote!(::derive_environment::map::KeyCase::#variant));
+	}
+}
+
+impl EnvFieldArgs {
+	fn has_default(&self) -> bool {
+		self.default.is_some() || self.default_with.is_some()
+	}
+
+	/// Bounds the field's type so that it can be loaded by [`EnvFieldArgs::load`], unless it has a parser.
+	fn load_bounds(&self) -> Vec<WherePredicate> {
+		let ty = &self.ty;
+		if self.vec_options().is_some() {
+			// `vec::with_env_options` requires its elements to implement `FromEnv` and `Default`.
+			match element_type(ty) {
+				Some(element) => vec![parse_quote!(
+					#element: ::derive_environment::FromEnv + ::std::default::Default
+				)],
+				None => vec![parse_quote!(#ty: ::derive_environment::FromEnv)],
+			}
+		} else if self.map_options().is_some() {
+			vec![
+				parse_quote!(#ty: ::derive_environment::map::EnvMap),
+				parse_quote!(
+					<<#ty as ::derive_environment::map::EnvMap>::Key as ::std::str::FromStr>::Err:
+						::std::convert::Into<::std::boxed::Box<dyn ::std::error::Error + ::std::marker::Send + ::std::marker::Sync>>
+				),
+			]
+		} else {
+			vec![parse_quote!(#ty: ::derive_environment::FromEnv)]
+		}
+	}
+
+	/// Returns an expression which evaluates to the field's default value.
+	fn default_value(&self, member: &Member) -> TokenStream {
+		if let Some(default) = &self.default {
+			let field = field_name(member);
+			if let Some(parser) = self.parser() {
+				quote!(::derive_environment::__private::parse_default_with(
+					#default, #field, #parser
+				))
+			} else if self.vec_options().is_some() || self.map_options().is_some() {
+				// The default is read just as the field's variable would be, such as splitting it on a delimiter.
+				let load = self.load(Mode::FailFast, &quote!(place), quote!(name));
+				quote!(::derive_environment::__private::load_default(
+					#default,
+					#field,
+					|place, source, name| ::derive_environment::Result::Ok(#load),
+				))
+			} else {
+				let ty = &self.ty;
+				quote! {{
+					use ::derive_environment::__private::{LoadDefault as _, ParseDefault as _};
+					(&::derive_environment::__private::DefaultParser::<#ty>::new())
+						.parse_default(#default, #field)
+				}}
+			}
+		} else if let Some(default_with) = &self.default_with {
+			quote!(#default_with())
+		} else {
+			quote!(::std::default::Default::default())
+		}
+	}
+
+	/// Returns the field's doc comment, with each line's leading space removed.
+	fn doc(&self) -> Option<String> {
+		let lines: Vec<String> = self
+			.attrs
+			.iter()
+			.filter_map(|attr| match &attr.meta {
+				Meta::NameValue(MetaNameValue {
+					value: Expr::Lit(ExprLit {
+						lit: Lit::Str(doc), ..
+					}),
+					..
+				}) => Some(doc.value()),
+				_ => None,
+			})
+			.map(|line| {
+				line.strip_prefix(' ')
+					.unwrap_or(&line)
+					.trim_end()
+					.to_string()
+			})
+			.collect();
+
+		let doc = lines.join("\n").trim().to_string();
+		(!doc.is_empty()).then_some(doc)
+	}
+
+	/// Returns the function used to parse the field, if it does not use `FromEnv`.
+	fn parser(&self) -> Option<TokenStream> {
+		if let Some(parse_with) = &self.parse_with {
+			Some(parse_with.to_token_stream())
+		} else if self.flag {
+			Some(quote!(::derive_environment::boolean::parse_flag))
+		} else {
+			self.with.as_ref().map(|with| quote!(#with::parse))
+		}
+	}
+
+	/// Returns the options passed to `derive_environment::vec`, if any were set.
+	fn vec_options(&self) -> Option<TokenStream> {
+		if self.delimiter.is_none() && self.indices.is_none() && self.vec_mode.is_none() {
+			return None;
+		}
+
+		let delimiter = self
+			.delimiter
+			.map(|delimiter| quote!(options.delimiter = ::std::option::Option::Some(#delimiter);));
+		let indices = self
+			.indices
+			.map(|indices| quote!(options.indices = #indices;));
+		let vec_mode = self.vec_mode.map(|mode| quote!(options.mode = #mode;));
+		Some(quote! {{
+			let mut options = ::derive_environment::vec::VecOptions::default();
+			#delimiter
+			#indices
+			#vec_mode
+			options
+		}})
+	}
+
+	/// Returns an expression which evaluates to the field's `MapOptions`, if any map attributes were given.
+	fn map_options(&self) -> Option<TokenStream> {
+		let key_case = self.key_case?;
+		Some(quote! {{
+			let mut options = ::derive_environment::map::MapOptions::default();
+			options.key_case = #key_case;
+			options
+		}})
+	}
+
+	/// Loads `place` from the variable `name`, evaluating to whether or not it was found.
+	fn load(&self, mode: Mode, place: &TokenStream, name: TokenStream) -> TokenStream {
+		if let Some(parser) = self.parser() {
+			mode.check(
+				quote!(::derive_environment::__private::parse_with(#place, source, #name, #parser)),
+				name,
+			)
+		} else if let Some(options) = self.vec_options() {
+			mode.load_options(quote!(::derive_environment::vec), place, name, options)
+		} else if let Some(options) = self.map_options() {
+			mode.load_options(quote!(::derive_environment::map), place, name, options)
+		} else {
+			mode.load(place, name)
+		}
+	}
+
+	fn validate(self) -> darling::Result<Self> {
+		if self.parse_with.is_some() && self.with.is_some() {
+			return Err(
+				darling::Error::custom("`parse_with` cannot be combined with `with`")
+					.with_span(&self.ty),
+			);
+		}
+		if self.flag && (self.parse_with.is_some() || self.with.is_some()) {
+			return Err(darling::Error::custom(
+				"`flag` cannot be combined with `parse_with` or `with`",
+			)
+			.with_span(&self.ty));
+		}
+		if self.default.is_some() && self.default_with.is_some() {
+			return Err(
+				darling::Error::custom("`default` cannot be combined with `default_with`")
+					.with_span(&self.ty),
+			);
+		}
+		if self.flatten && (self.rename.is_some() || !self.alias.is_empty()) {
+			return Err(darling::Error::custom(
+				"`flatten` cannot be combined with `rename` or `alias`",
+			)
+			.with_span(&self.ty));
+		}
+		if self.key_case.is_some() && self.vec_options().is_some() {
+			return Err(darling::Error::custom(
+				"`key_case` cannot be combined with `delimiter`, `indices` or `vec_mode`",
+			)
+			.with_span(&self.ty));
+		}
+		Ok(self)
+	}
 }
 
-/// Generates a `load_environment()` function that will populate each field from environment variables.
+/// Implements `FromEnv`, populating each field from environment variables.
+///
+/// Fields are read from `PREFIX_FIELD`, where `PREFIX` is the variable passed to `with_env`.
+/// Note that `with_env()` is not a constructor.
 ///
-/// This uses `.parse()` so make sure all members implement `FromStr`.
-/// Note that `load_environment()` is not a constructor.
-#[proc_macro_derive(FromEnv, attributes(prefix, env))]
+/// Fields marked `#[env(required)]` fail with `FromEnvError::Missing` if their variable is absent.
+/// `#[env(deny_missing)]` on the container makes every field required.
+///
+/// The types of fields using generic type parameters are bounded by `FromEnv`, such as `Vec<T>: FromEnv`.
+/// `#[env(bound = "T: FromEnv + Default")]` on the container replaces these bounds,
+/// for both the `FromEnv` and `EnvSchema` implementations.
+///
+/// `#[env(parse_with = function)]` parses a field using a `fn(&str) -> Result<T, E>` instead of its `FromEnv` implementation.
+/// `#[env(with = module)]` does the same using `module::parse`.
+///
+/// `#[env(redact)]` removes a field's value, and the parser's error, from any `FromEnvError::ParseError` it causes.
+///
+/// `#[env(flag)]` reads a `bool` which is `true` when its variable is set but empty.
+///
+/// `#[env(file)]` reads each of a field's variables from the file named by `VAR_FILE` when `VAR` is absent,
+/// less a trailing newline, following the convention for Docker and Kubernetes secrets.
+/// This includes the variables of nested types, such as `PREFIX_FIELD_PORT_FILE`, and of aliases.
+/// Files which cannot be read fail with `FromEnvError::Io`.
+/// `#[env(file)]` on the container applies this to every field.
+///
+/// `#[env(delimiter = ",")]` reads a `Vec` from a single variable separated by the given character,
+/// falling back to indexed variables if it is absent.
+///
+/// `#[env(indices = "compact")]` reads every `PREFIX_<n>` of a `Vec` in order, even if some indices are absent,
+/// and `#[env(indices = "strict")]` fails with `FromEnvError::IndexGap` if any are.
+///
+/// `#[env(vec_mode = "merge")]` loads each index of a `Vec` into its existing element,
+/// and `#[env(vec_mode = "append")]` pushes new elements after the existing ones.
+/// The default, `"replace"`, replaces the whole vector.
+///
+/// `#[env(key_case = "lower")]` converts the keys of a `HashMap` or `BTreeMap` before they are parsed.
+/// The other cases are `"preserve"` (the default), `"upper"` and `"kebab"`.
+///
+/// `#[env(default)]` on a struct implements `Default`,
+/// using `#[env(default = "...")]` (parsed using `FromStr`, or read like the field's variable) or `#[env(default_with = function)]` on each field.
+/// Fields without either use their type's `Default`.
+/// These defaults are only used when constructing the struct; `with_env()` never resets a field.
+///
+/// Newtypes read their only field from `PREFIX` itself,
+/// while other tuple structs read their fields from `PREFIX_0`, `PREFIX_1`, and so on.
+///
+/// `EnvSchema` is also implemented, listing each variable along with its field's doc comment.
+/// The types of fields using type parameters are bounded by `EnvSchema`.
+/// `#[env(skip_schema)]` leaves a field out of it, for field types which only implement `FromEnv`,
+/// and `#[env(schema = false)]` on the container skips the implementation entirely.
+///
+/// Enums containing only unit variants are read from a single variable holding the variant's name.
+/// Other enums select their variant using `PREFIX_KIND` (or `#[env(tag = "...")]`),
+/// and then read that variant's fields like a struct would.
+#[proc_macro_derive(FromEnv, attributes(env))]
 pub fn environment(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 	// Parse the input tokens into a syntax tree
 	let input = parse_macro_input!(input as DeriveInput);
@@ -42,51 +628,574 @@ pub fn environment(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
 		}
 	};
 
-	let name = input.ident;
-	let fields = args.data.as_ref().take_struct().unwrap().fields;
-	let parseable_fields = env_from_parseable(&fields);
+	let name = &args.ident;
+	let generics = args.env_generics();
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let separator = args.set_separator();
+	let fail_fast = body(&args, Mode::FailFast);
+	let collect = body(&args, Mode::Collect);
+	let default_impl = default_impl(&args);
+	let schema_impl = schema_impl(&args);
+	let prefix = args
+		.prefix
+		.as_ref()
+		.map(|prefix| quote!(const PREFIX: &'static str = #prefix;));
 
 	// Build the output, possibly using quasi-quotation
 	let expanded = quote! {
-		impl ::derive_environment::FromEnv for #name {
-			fn with_env(&mut self, prefix: &str) -> ::derive_environment::Result<bool> {
-				// Tracks whether or not a variable was found.
-				// Important for nested extendables.
-				let mut found_match = false;
-				#parseable_fields
-				::derive_environment::Result::Ok(found_match)
+		impl #impl_generics ::derive_environment::FromEnv for #name #ty_generics #where_clause {
+			#prefix
+
+			fn with_env_from(
+				&mut self,
+				source: &dyn ::derive_environment::EnvSource,
+				prefix: &str,
+			) -> ::derive_environment::Result<bool> {
+				#separator
+				#fail_fast
+			}
+
+			fn collect_env_from(
+				&mut self,
+				source: &dyn ::derive_environment::EnvSource,
+				prefix: &str,
+				errors: &mut ::derive_environment::FromEnvErrors,
+			) -> bool {
+				#separator
+				#collect
 			}
 		}
+
+		#default_impl
+		#schema_impl
 	};
 
 	// Hand the output tokens back to the compiler
 	expanded.into()
 }
 
-fn to_variable(field: &&EnvFieldArgs) -> String {
-	field
-		.ident
-		.clone()
-		.unwrap()
-		.to_string()
-		.to_case(Case::UpperSnake)
+/// Implements `Default` if requested by `#[env(default)]`.
+fn default_impl(args: &EnvArgs) -> TokenStream {
+	let fields = match &args.data {
+		ast::Data::Struct(fields) if args.default => fields,
+		_ => return TokenStream::new(),
+	};
+
+	let name = &args.ident;
+	let generics = args.default_generics();
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let defaults = members(fields).map(|(field, member)| {
+		let value = field.default_value(&member);
+		quote!(#member: #value)
+	});
+
+	quote! {
+		impl #impl_generics ::std::default::Default for #name #ty_generics #where_clause {
+			fn default() -> Self {
+				Self { #(#defaults),* }
+			}
+		}
+	}
 }
 
-fn to_field(field: &&EnvFieldArgs) -> Ident {
-	field.ident.clone().unwrap()
+/// Implements `EnvSchema`, listing the variables read by the generated `FromEnv` implementation.
+fn schema_impl(args: &EnvArgs) -> TokenStream {
+	if args.schema == Some(false) {
+		return TokenStream::new();
+	}
+
+	let name = &args.ident;
+	let generics = args.schema_generics();
+	let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+	let separator = args.set_separator();
+
+	let body = match &args.data {
+		ast::Data::Struct(fields) => {
+			let fields = fields_schema(args, &name.to_string(), fields);
+			quote! {
+				let mut vars = ::std::vec::Vec::new();
+				#fields
+				vars
+			}
+		}
+		ast::Data::Enum(variants) if variants.iter().all(|v| v.fields.is_unit()) => {
+			let names = variant_names(variants);
+			quote!(
+				::std::vec![::derive_environment::__private::variant_schema::<Self>(
+					prefix, #names
+				)]
+			)
+		}
+		ast::Data::Enum(variants) => {
+			let tag = to_name(Some(&args.tag()));
+			let names = variant_names(variants);
+			let fields = variants.iter().map(|variant| {
+				let parent = format!("{}::{}", name, variant.ident);
+				fields_schema(args, &parent, &variant.fields)
+			});
+			quote! {
+				let mut vars = ::std::vec![::derive_environment::__private::variant_schema::<Self>(
+					&#tag, #names
+				)];
+				#(#fields)*
+				vars
+			}
+		}
+	};
+
+	quote! {
+		impl #impl_generics ::derive_environment::EnvSchema for #name #ty_generics #where_clause {
+			fn env_schema(prefix: &str) -> ::std::vec::Vec<::derive_environment::EnvVar> {
+				#separator
+				#body
+			}
+		}
+	}
+}
+
+/// Pushes the variables of each field to `vars`.
+///
+/// `parent` names the structure or variant the fields belong to.
+fn fields_schema(args: &EnvArgs, parent: &str, fields: &ast::Fields<EnvFieldArgs>) -> TokenStream {
+	let newtype = fields.style == ast::Style::Tuple && fields.len() == 1;
+
+	let fields = members(fields)
+		.filter(|(field, _)| !field.ignore && !field.skip_schema)
+		.map(|(field, member)| {
+			let name = to_name(to_variable(args, field, &member, newtype).as_deref());
+			let ty = &field.ty;
+			let field_vars = if field.flag {
+				quote!(::std::vec![::derive_environment::EnvVar::leaf_with::<#ty>(
+					&name,
+					::derive_environment::schema::ValueKind::Flag,
+				)])
+			} else if field.parser().is_some() {
+				quote!(::std::vec![::derive_environment::EnvVar::leaf::<#ty>(&name)])
+			} else if field.delimiter.is_some() {
+				quote!(::derive_environment::__private::delimited_schema::<#ty>(&name))
+			} else {
+				quote!(<#ty as ::derive_environment::EnvSchema>::env_schema(&name))
+			};
+			let field_vars = if field.file || args.file {
+				quote!(::derive_environment::__private::file_schema(#field_vars))
+			} else {
+				field_vars
+			};
+
+			let field_name = field_name(&member);
+			let doc = match field.doc() {
+				Some(doc) => quote!(::std::option::Option::Some(#doc)),
+				None => quote!(::std::option::Option::None),
+			};
+			let default = match &field.default {
+				Some(default) => quote!(::std::option::Option::Some(#default)),
+				None => quote!(::std::option::Option::None),
+			};
+			let required = field.required || args.deny_missing;
+
+			quote! {
+				let name = #name;
+				vars.extend(::derive_environment::__private::describe_field(
+					#field_vars,
+					&name,
+					#parent,
+					#field_name,
+					#doc,
+					#default,
+					#required,
+				));
+			}
+		});
+
+	quote!(#(#fields)*)
+}
+
+fn body(args: &EnvArgs, mode: Mode) -> TokenStream {
+	match &args.data {
+		ast::Data::Struct(fields) => struct_body(args, fields, mode),
+		ast::Data::Enum(variants) if variants.iter().all(|v| v.fields.is_unit()) => {
+			unit_enum_body(variants, mode)
+		}
+		ast::Data::Enum(variants) => data_enum_body(args, variants, mode),
+	}
 }
 
-fn env_from_parseable(fields: &[&EnvFieldArgs]) -> TokenStream {
+fn struct_body(args: &EnvArgs, fields: &ast::Fields<EnvFieldArgs>, mode: Mode) -> TokenStream {
+	let parent = args.ident.to_string();
+	let loaded_fields = env_from_parseable(
+		args,
+		&parent,
+		fields,
+		|member| quote!(&mut self.#member),
+		mode,
+	);
+	let finish = mode.finish(quote!(found_match));
+
+	quote! {
+		// Tracks whether or not a variable was found.
+		// Important for nested extendables.
+		let mut found_match = false;
+		#loaded_fields
+		#finish
+	}
+}
+
+fn unit_enum_body(variants: &[EnvVariantArgs], mode: Mode) -> TokenStream {
+	let names = variant_names(variants);
+	let read = mode.check(
+		quote!(::derive_environment::__private::read_variant(source, prefix, #names)),
+		quote!(prefix),
+	);
+	let arms = variants.iter().enumerate().map(|(i, variant)| {
+		let v = &variant.ident;
+		quote!(::std::option::Option::Some(#i) => *self = Self::#v,)
+	});
+	let finish = mode.finish(quote!(variant.is_some()));
+
+	quote! {
+		let variant: ::std::option::Option<usize> = #read;
+		match variant {
+			#(#arms)*
+			::std::option::Option::Some(_) => ::std::unreachable!(),
+			::std::option::Option::None => {}
+		}
+		#finish
+	}
+}
+
+fn data_enum_body(args: &EnvArgs, variants: &[EnvVariantArgs], mode: Mode) -> TokenStream {
+	let tag = to_name(Some(&args.tag()));
+	let names = variant_names(variants);
+	let read = mode.check(
+		quote!(::derive_environment::__private::read_variant(source, &tag, #names)),
+		quote!(&tag),
+	);
+
+	// Switching variants discards the previous fields, so the new variant starts from defaults.
+	let select_arms = variants.iter().enumerate().map(|(i, variant)| {
+		let v = &variant.ident;
+		let defaults = members(&variant.fields)
+			.map(|(_, member)| quote!(#member: ::std::default::Default::default()));
+		quote! {
+			::std::option::Option::Some(#i) => if !::std::matches!(self, Self::#v { .. }) {
+				*self = Self::#v { #(#defaults),* };
+			},
+		}
+	});
+
+	let load_arms = variants.iter().map(|variant| {
+		let v = &variant.ident;
+		// Bindings are renamed so that they cannot shadow the generated code's locals.
+		let bindings = members(&variant.fields)
+			.filter(|(field, _)| !field.ignore)
+			.map(|(_, member)| {
+				let binding = to_binding(&member);
+				quote!(#member: #binding)
+			});
+		let parent = format!("{}::{}", args.ident, variant.ident);
+		let loaded_fields = env_from_parseable(
+			args,
+			&parent,
+			&variant.fields,
+			|member| to_binding(member).into_token_stream(),
+			mode,
+		);
+		quote! {
+			Self::#v { #(#bindings,)* .. } => { #loaded_fields }
+		}
+	});
+	let finish = mode.finish(quote!(found_match));
+
+	quote! {
+		let mut found_match = false;
+
+		let tag = #tag;
+		let variant: ::std::option::Option<usize> = #read;
+		if variant.is_some() {
+			found_match = true;
+		}
+		match variant {
+			#(#select_arms)*
+			::std::option::Option::Some(_) => ::std::unreachable!(),
+			::std::option::Option::None => {}
+		}
+
+		match self {
+			#(#load_arms)*
+		}
+		#finish
+	}
+}
+
+/// Returns `T` from a type written as `Vec<T>` (or any other path ending in a single type argument).
+fn element_type(ty: &Type) -> Option<&Type> {
+	let Type::Path(path) = ty else {
+		return None;
+	};
+	let PathArguments::AngleBracketed(arguments) = &path.path.segments.last()?.arguments else {
+		return None;
+	};
+	match arguments.args.iter().collect::<Vec<_>>().as_slice() {
+		[GenericArgument::Type(element)] => Some(element),
+		_ => None,
+	}
+}
+
+/// Lists the names each variant may be selected by, in declaration order.
+fn variant_names(variants: &[EnvVariantArgs]) -> TokenStream {
+	let names = variants.iter().map(|variant| {
+		if let Some(rename) = &variant.rename {
+			return quote!(&[#rename]);
+		}
+
+		let ident = variant.ident.to_string();
+		let snake = ident.to_case(Case::Snake);
+		if snake.eq_ignore_ascii_case(&ident) {
+			quote!(&[#ident])
+		} else {
+			quote!(&[#ident, #snake])
+		}
+	});
+
+	quote!(&[#(#names),*])
+}
+
+/// Determines how generated code reacts to a field which fails to load.
+#[derive(Clone, Copy)]
+enum Mode {
+	/// Return the first error encountered (`with_env_from`).
+	FailFast,
+	/// Push errors to `errors` and continue (`collect_env_from`).
+	Collect,
+}
+
+impl Mode {
+	/// Unwraps `result`, an error in which is attributed to the variable `var`.
+	///
+	/// When collecting, errors evaluate to the default value of `result`'s type.
+	fn check(self, result: TokenStream, var: TokenStream) -> TokenStream {
+		match self {
+			Mode::FailFast => quote!(#result?),
+			Mode::Collect => quote! {
+				match #result {
+					::std::result::Result::Ok(value) => value,
+					::std::result::Result::Err(error) => {
+						errors.push(#var, error);
+						::std::default::Default::default()
+					}
+				}
+			},
+		}
+	}
+
+	/// Returns `found` from the generated function.
+	fn finish(self, found: TokenStream) -> TokenStream {
+		match self {
+			Mode::FailFast => quote!(::derive_environment::Result::Ok(#found)),
+			Mode::Collect => found,
+		}
+	}
+
+	/// Reports `error`, which is attributed to the variable `var`.
+	fn fail(self, error: TokenStream, var: TokenStream) -> TokenStream {
+		match self {
+			Mode::FailFast => quote!(return ::derive_environment::Result::Err(#error);),
+			Mode::Collect => quote!(errors.push(#var, #error);),
+		}
+	}
+
+	/// Loads `place` using `module`'s `with_env_options` or `collect_env_options` functions.
+	fn load_options(
+		self,
+		module: TokenStream,
+		place: &TokenStream,
+		name: TokenStream,
+		options: TokenStream,
+	) -> TokenStream {
+		match self {
+			Mode::FailFast => quote! {
+				#module::with_env_options(#place, source, #name, &#options)?
+			},
+			Mode::Collect => quote! {
+				#module::collect_env_options(#place, source, #name, &#options, errors)
+			},
+		}
+	}
+
+	/// Evaluates `load`, recording the field `name` of `parent` in any errors it throws.
+	///
+	/// If `redact` is set, the variable's value is also removed from those errors.
+	fn field(self, parent: &str, name: &str, redact: bool, load: TokenStream) -> TokenStream {
+		match self {
+			Mode::FailFast => quote! {
+				::derive_environment::__private::field(#parent, #name, #redact, || {
+					let found = #load;
+					::derive_environment::Result::Ok(found)
+				})?
+			},
+			Mode::Collect => quote! {
+				::derive_environment::__private::collect_field(
+					errors,
+					#parent,
+					#name,
+					#redact,
+					|errors| #load,
+				)
+			},
+		}
+	}
+
+	/// Loads `place` from the variable `name`, evaluating to whether or not it was found.
+	fn load(self, place: &TokenStream, name: TokenStream) -> TokenStream {
+		match self {
+			Mode::FailFast => quote! {
+				::derive_environment::FromEnv::with_env_from(#place, source, #name)?
+			},
+			Mode::Collect => quote! {
+				::derive_environment::FromEnv::collect_env_from(#place, source, #name, errors)
+			},
+		}
+	}
+}
+
+/// Returns the segment appended to the prefix to name a field's variable.
+///
+/// Newtypes and flattened fields are transparent, so they have no segment of their own.
+fn to_variable(
+	args: &EnvArgs,
+	field: &EnvFieldArgs,
+	member: &Member,
+	newtype: bool,
+) -> Option<String> {
+	if let Some(rename) = &field.rename {
+		return Some(rename.clone());
+	}
+
+	match member {
+		_ if field.flatten => None,
+		Member::Named(ident) => Some(ident.to_string().to_case(args.case())),
+		Member::Unnamed(_) if newtype => None,
+		Member::Unnamed(index) => Some(index.index.to_string()),
+	}
+}
+
+/// Builds a variable name by appending `segment` to the prefix.
+fn to_name(segment: Option<&str>) -> TokenStream {
+	match segment {
+		Some(segment) => quote!(::derive_environment::__private::join(prefix, #segment)),
+		None => quote!(::std::string::String::from(prefix)),
+	}
+}
+
+/// Pairs each field with the member used to access it (`self.name` or `self.0`).
+fn members(fields: &ast::Fields<EnvFieldArgs>) -> impl Iterator<Item = (&EnvFieldArgs, Member)> {
+	fields.iter().enumerate().map(|(i, field)| {
+		let member = match &field.ident {
+			Some(ident) => Member::Named(ident.clone()),
+			None => Member::Unnamed(Index::from(i)),
+		};
+		(field, member)
+	})
+}
+
+/// Returns the name of a field as written in the source code, such as `port` or `0`.
+fn field_name(member: &Member) -> String {
+	match member {
+		Member::Named(ident) => ident.unraw().to_string(),
+		Member::Unnamed(index) => index.index.to_string(),
+	}
+}
+
+/// Names the binding a variant's field is destructured into.
+///
+/// Bindings are prefixed so that they cannot shadow the generated code's locals.
+fn to_binding(member: &Member) -> Ident {
+	match member {
+		Member::Named(ident) => format_ident!("__{ident}"),
+		Member::Unnamed(index) => format_ident!("__{}", index.index),
+	}
+}
+
+/// Loads each field from its variable.
+///
+/// `place` produces an expression evaluating to a mutable reference to the given field.
+/// `parent` names the structure or variant the fields belong to.
+fn env_from_parseable(
+	args: &EnvArgs,
+	parent: &str,
+	fields: &ast::Fields<EnvFieldArgs>,
+	place: impl Fn(&Member) -> TokenStream,
+	mode: Mode,
+) -> TokenStream {
 	let mut tokens = TokenStream::new();
+	let newtype = fields.style == ast::Style::Tuple && fields.len() == 1;
+
+	for (field, member) in members(fields).filter(|(x, _)| !x.ignore) {
+		let place = place(&member);
+		let name = to_name(to_variable(args, field, &member, newtype).as_deref());
+		let load = field.load(mode, &place, quote!(&name));
+
+		// Aliases are only consulted if every name before them was absent.
+		let aliases = field.alias.iter().map(|alias| {
+			let name = to_name(Some(alias));
+			let load = field.load(mode, &place, quote!(&name));
+			quote!(|| {
+				let name = #name;
+				#load
+			})
+		});
+
+		let (count, missing) = if field.required || args.deny_missing {
+			let fail = mode.fail(
+				quote!(::derive_environment::FromEnvError::missing(&name)),
+				quote!(&name),
+			);
+			match mode {
+				Mode::FailFast => (TokenStream::new(), quote!(if !found { #fail })),
+				// A variable which failed to parse was present, so it is not also missing.
+				Mode::Collect => (
+					quote!(let error_count = errors.len();),
+					quote!(if !found && errors.len() == error_count { #fail }),
+				),
+			}
+		} else {
+			(TokenStream::new(), TokenStream::new())
+		};
+
+		// Files stand in for every variable read by the field, including those of nested types and aliases.
+		let file = if field.file || args.file {
+			let finish = match mode {
+				Mode::FailFast => quote!(files.check()?;),
+				Mode::Collect => quote!(files.collect(errors);),
+			};
+			quote! {
+				let files = ::derive_environment::__private::FileSource::new(source);
+				let found = {
+					let source: &dyn ::derive_environment::EnvSource = &files;
+					#load #(#aliases)*
+				};
+				#finish
+			}
+		} else {
+			quote!(let found = #load #(#aliases)*;)
+		};
 
-	for field in fields.iter().filter(|x| !x.ignore) {
-		let f = to_field(field);
-		let var = to_variable(field);
+		let load = mode.field(
+			parent,
+			&field_name(&member),
+			field.redact,
+			quote! {{
+				#count
+				#file
+				#missing
+				found
+			}},
+		);
 
 		tokens.extend(quote! {
-			let name = ::std::format!("{prefix}_{}", #var);
+			let name = #name;
 
-			if derive_environment::FromEnv::with_env(&mut self.#f, &name)? {
+			if #load {
 				found_match = true;
 			}
 		});
@@ -0,0 +1,46 @@
+//! Prints the variables read by a config structure, as a `.env.example` file or a table.
+//!
+//! Run this as `cargo run --example reference -- [dotenv|markdown|text|json]`.
+
+use derive_environment::{schema, units::ByteSize, EnvSchema, FromEnv};
+use std::{env, time::Duration};
+
+#[derive(Debug, FromEnv)]
+#[env(default)]
+struct Database {
+	/// Address of the database server.
+	#[env(required)]
+	url: String,
+	/// Connections kept open at once.
+	#[env(default = "10")]
+	pool_size: u32,
+}
+
+#[derive(Debug, FromEnv)]
+#[env(default)]
+struct Config {
+	database: Database,
+	/// Hosts allowed to connect.
+	allowed_hosts: Vec<String>,
+	/// How long to wait for a request before giving up.
+	timeout: Duration,
+	/// Largest request body accepted.
+	#[env(default = "1MiB")]
+	max_body: ByteSize,
+}
+
+fn main() {
+	let vars = Config::env_schema("MY_APP");
+	let format = env::args().nth(1);
+	let output = match format.as_deref() {
+		None | Some("dotenv") => schema::dotenv_example(&vars),
+		Some("markdown") => schema::markdown_table(&vars),
+		Some("text") => schema::text_table(&vars),
+		Some("json") => schema::json_schema(&vars),
+		Some(format) => {
+			eprintln!("unknown format {format:?}; expected dotenv, markdown, text or json");
+			std::process::exit(2);
+		}
+	};
+	print!("{output}");
+}
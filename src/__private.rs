@@ -0,0 +1,358 @@
+//! Implementation details of `#[derive(FromEnv)]`.
+//!
+//! Nothing in this module is considered public API.
+
+use crate::{
+	schema::ValueKind, EnvSchema, EnvSource, EnvVar, FromEnv, FromEnvError, FromEnvErrors,
+	PathSegment, Result,
+};
+use std::{
+	cell::{Cell, RefCell},
+	collections::HashMap,
+	env::VarError,
+	error::Error,
+	ffi::OsString,
+	fmt::Display,
+	fs,
+	marker::PhantomData,
+	str::FromStr,
+};
+
+thread_local! {
+	/// The separator of the innermost derived type being loaded or listed which set one.
+	static SEPARATOR: Cell<&'static str> = const { Cell::new("_") };
+}
+
+/// Returns the separator placed between a prefix and the names beneath it.
+///
+/// This is `_`, unless a derived type with `#[env(separator = "...")]` is being loaded or listed,
+/// so that its nested types, vector indices and map keys use its separator too.
+pub fn separator() -> &'static str {
+	SEPARATOR.with(Cell::get)
+}
+
+/// Sets the separator returned by [`separator`] until the returned guard is dropped.
+pub fn set_separator(separator: &'static str) -> SeparatorGuard {
+	SeparatorGuard(SEPARATOR.with(|current| current.replace(separator)))
+}
+
+/// Restores the previous separator when dropped.
+pub struct SeparatorGuard(&'static str);
+
+impl Drop for SeparatorGuard {
+	fn drop(&mut self) {
+		SEPARATOR.with(|current| current.set(self.0));
+	}
+}
+
+/// Appends `segment` to `prefix` using the current [`separator`], unless the prefix is empty.
+pub fn join(prefix: &str, segment: &str) -> String {
+	if prefix.is_empty() {
+		segment.to_string()
+	} else {
+		format!("{prefix}{}{segment}", separator())
+	}
+}
+
+/// Parses the `#[env(default = "...")]` of a field of type `T`.
+///
+/// Calling `(&DefaultParser::<T>::new()).parse_default(...)` uses [`ParseDefault`] if `T` implements `FromStr`,
+/// so that types without a `Default` implementation may have defaults.
+/// Otherwise, method resolution falls back to [`LoadDefault`], which loads the default like a variable.
+pub struct DefaultParser<T>(PhantomData<T>);
+
+impl<T> DefaultParser<T> {
+	#[allow(clippy::new_without_default)]
+	pub fn new() -> Self {
+		Self(PhantomData)
+	}
+}
+
+/// Parses defaults using `FromStr`.
+pub trait ParseDefault<T> {
+	fn parse_default(&self, value: &str, field: &str) -> T;
+}
+
+impl<T> ParseDefault<T> for DefaultParser<T>
+where
+	T: FromStr,
+	T::Err: Display,
+{
+	fn parse_default(&self, value: &str, field: &str) -> T {
+		parse_default_with(value, field, str::parse)
+	}
+}
+
+/// Parses defaults using `FromEnv`, for types such as `Duration` which don't implement `FromStr`.
+pub trait LoadDefault<T> {
+	fn parse_default(&self, value: &str, field: &str) -> T;
+}
+
+impl<T: FromEnv + Default> LoadDefault<T> for &DefaultParser<T> {
+	fn parse_default(&self, value: &str, field: &str) -> T {
+		load_default(value, field, T::with_env_from)
+	}
+}
+
+/// Loads the `#[env(default = "...")]` of `field` using `load`, as if it were the value of the field's variable.
+///
+/// Defaults are written in the source code, so a default which fails to parse, or is never read, is a bug.
+pub fn load_default<T: Default>(
+	value: &str,
+	field: &str,
+	load: impl FnOnce(&mut T, &dyn EnvSource, &str) -> Result<bool>,
+) -> T {
+	let mut default = T::default();
+	match load(&mut default, &[(field, value)], field) {
+		Ok(true) => default,
+		Ok(false) => panic!("default {value:?} for field `{field}` was never read"),
+		Err(error) => {
+			// The parser's own error says more than one naming a variable that doesn't exist.
+			let reason = error
+				.source()
+				.map_or_else(|| error.to_string(), ToString::to_string);
+			panic!("invalid default {value:?} for field `{field}`: {reason}");
+		}
+	}
+}
+
+/// Parses the `#[env(default = "...")]` of `field` using the field's `#[env(parse_with)]` or `#[env(with)]` function.
+pub fn parse_default_with<T, E: Display>(
+	value: &str,
+	field: &str,
+	parse: impl FnOnce(&str) -> std::result::Result<T, E>,
+) -> T {
+	parse(value)
+		.unwrap_or_else(|error| panic!("invalid default {value:?} for field `{field}`: {error}"))
+}
+
+/// Reads `var` into `place` using `parse` instead of `FromEnv`.
+pub fn parse_with<T, E: Into<Box<dyn Error + Send + Sync>>>(
+	place: &mut T,
+	source: &dyn EnvSource,
+	var: &str,
+	parse: impl FnOnce(&str) -> std::result::Result<T, E>,
+) -> Result<bool> {
+	match source.var(var) {
+		Ok(value) => {
+			*place =
+				parse(&value).map_err(|error| FromEnvError::parse_error(var, &value, error))?;
+			Ok(true)
+		}
+		Err(VarError::NotPresent) => Ok(false),
+		Err(VarError::NotUnicode(s)) => Err(FromEnvError::not_unicode(var, s)),
+	}
+}
+
+/// Reads `var` and returns the index of the variant it names, or `None` if it is absent.
+///
+/// Each variant may be named in several ways; names are compared case-insensitively.
+pub fn read_variant(
+	source: &dyn EnvSource,
+	var: &str,
+	variants: &[&[&str]],
+) -> Result<Option<usize>> {
+	let value = match source.var(var) {
+		Ok(value) => value,
+		Err(VarError::NotPresent) => return Ok(None),
+		Err(VarError::NotUnicode(s)) => return Err(FromEnvError::not_unicode(var, s)),
+	};
+
+	variants
+		.iter()
+		.position(|names| names.iter().any(|name| name.eq_ignore_ascii_case(&value)))
+		.map(Some)
+		.ok_or_else(|| {
+			let expected = variants
+				.iter()
+				.filter_map(|names| names.first())
+				.copied()
+				.collect::<Vec<_>>()
+				.join(", ");
+			FromEnvError::parse_error(
+				var,
+				&value,
+				format!("unknown variant {value:?}, expected one of: {expected}"),
+			)
+		})
+}
+
+/// Describes the variable an enum is selected by, which accepts any of the names in `variants`.
+pub fn variant_schema<T>(var: &str, variants: &[&[&str]]) -> EnvVar {
+	let names = variants
+		.iter()
+		.copied()
+		.flatten()
+		.map(|name| name.to_string());
+	EnvVar::leaf_with::<T>(var, ValueKind::OneOf(names.collect()))
+}
+
+/// Reads variables from `source` for fields marked `#[env(file)]`,
+/// falling back to the file named by `{VAR}_FILE` for each variable which is absent.
+///
+/// Files are read less a trailing newline.
+/// A file which cannot be read leaves its variable absent, and the error is kept until [`FileSource::check`] or [`FileSource::collect`].
+pub struct FileSource<'a> {
+	source: &'a dyn EnvSource,
+	/// Values read from files, so that each file is only read once.
+	read: RefCell<HashMap<String, Option<OsString>>>,
+	errors: RefCell<Vec<FromEnvError>>,
+}
+
+impl<'a> FileSource<'a> {
+	/// Wraps `source`.
+	pub fn new(source: &'a dyn EnvSource) -> Self {
+		Self {
+			source,
+			read: RefCell::default(),
+			errors: RefCell::default(),
+		}
+	}
+
+	/// Returns the first error thrown while reading a file.
+	pub fn check(self) -> Result<()> {
+		match self.errors.into_inner().into_iter().next() {
+			Some(error) => Err(error),
+			None => Ok(()),
+		}
+	}
+
+	/// Pushes every error thrown while reading a file to `errors`.
+	pub fn collect(self, errors: &mut FromEnvErrors) {
+		for error in self.errors.into_inner() {
+			errors.push(error.var().to_string(), error);
+		}
+	}
+}
+
+impl EnvSource for FileSource<'_> {
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		if let Some(value) = self.source.var_os(key) {
+			return Some(value);
+		}
+		if let Some(value) = self.read.borrow().get(key) {
+			return value.clone();
+		}
+
+		let file_var = format!("{key}_FILE");
+		let file = self.source.var_os(&file_var)?;
+		let value = match fs::read_to_string(&file) {
+			Ok(mut contents) => {
+				if contents.ends_with('\n') {
+					contents.pop();
+					if contents.ends_with('\r') {
+						contents.pop();
+					}
+				}
+				Some(OsString::from(contents))
+			}
+			Err(error) => {
+				let error = FromEnvError::io(&file_var, file, error);
+				self.errors.borrow_mut().push(error);
+				None
+			}
+		};
+
+		self.read
+			.borrow_mut()
+			.insert(key.to_string(), value.clone());
+		value
+	}
+
+	fn keys(&self) -> Vec<String> {
+		// Each file stands in for the variable it is named after.
+		let mut keys: Vec<String> = self
+			.source
+			.keys()
+			.into_iter()
+			.map(|key| match key.strip_suffix("_FILE") {
+				Some(var) => var.to_string(),
+				None => key,
+			})
+			.collect();
+		keys.sort();
+		keys.dedup();
+		keys
+	}
+}
+
+/// Loads the field `name` of `parent` using `load`, recording the field in any error it throws.
+pub fn field(
+	parent: &'static str,
+	name: &'static str,
+	redact: bool,
+	load: impl FnOnce() -> Result<bool>,
+) -> Result<bool> {
+	load().map_err(|mut error| {
+		scope(&mut error, parent, name, redact);
+		error
+	})
+}
+
+/// Like [`field`], but records the field in every error `load` pushes to `errors`.
+pub fn collect_field(
+	errors: &mut FromEnvErrors,
+	parent: &'static str,
+	name: &'static str,
+	redact: bool,
+	load: impl FnOnce(&mut FromEnvErrors) -> bool,
+) -> bool {
+	let start = errors.len();
+	let found = load(errors);
+	for (_, error) in &mut errors.errors[start..] {
+		scope(error, parent, name, redact);
+	}
+	found
+}
+
+fn scope(error: &mut FromEnvError, parent: &'static str, name: &'static str, redact: bool) {
+	error.within(PathSegment::Field { parent, name });
+	if redact {
+		error.redact();
+	}
+}
+
+/// Records that `vars` were read by the field `field` of `parent`, whose own variable is `name`.
+pub fn describe_field(
+	mut vars: Vec<EnvVar>,
+	name: &str,
+	parent: &'static str,
+	field: &'static str,
+	doc: Option<&str>,
+	default: Option<&str>,
+	required: bool,
+) -> Vec<EnvVar> {
+	for var in &mut vars {
+		var.within(PathSegment::Field {
+			parent,
+			name: field,
+		});
+		if var.doc.is_none() {
+			var.doc = doc.map(String::from);
+		}
+		// Nested variables have their own defaults, and are required by their own fields.
+		if var.name == name {
+			if default.is_some() {
+				var.default = default.map(String::from);
+			}
+			var.required |= required;
+		}
+	}
+	vars
+}
+
+/// Records that each of the variables of a field marked `#[env(file)]` may be read from `{VAR}_FILE`.
+pub fn file_schema(mut vars: Vec<EnvVar>) -> Vec<EnvVar> {
+	for var in &mut vars {
+		var.file = true;
+	}
+	vars
+}
+
+/// Lists the variables of a field marked `#[env(delimiter = "...")]`:
+/// the delimited variable, followed by the indexed variables it falls back to.
+pub fn delimited_schema<T: EnvSchema>(name: &str) -> Vec<EnvVar> {
+	let mut vars = vec![EnvVar::leaf::<T>(name)];
+	vars.extend(T::env_schema(name));
+	vars
+}
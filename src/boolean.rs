@@ -0,0 +1,91 @@
+//! Reading booleans from the many ways they are commonly written.
+
+use crate::{EnvSource, FromEnv, Result};
+
+/// Values accepted as `true`, compared case-insensitively.
+pub(crate) const TRUTHY: &[&str] = &["true", "t", "yes", "y", "on", "1", "enable", "enabled"];
+
+/// Values accepted as `false`, compared case-insensitively.
+pub(crate) const FALSY: &[&str] = &["false", "f", "no", "n", "off", "0", "disable", "disabled"];
+
+/// Thrown when a value is not a recognized boolean.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("expected a boolean (such as true/false, yes/no, on/off or 1/0), found {0:?}")]
+pub struct BoolError(pub String);
+
+/// Parses a boolean, accepting common spellings of `true` and `false`.
+///
+/// `true`, `t`, `yes`, `y`, `on`, `1`, `enable` and `enabled` are true,
+/// while `false`, `f`, `no`, `n`, `off`, `0`, `disable` and `disabled` are false.
+/// Values are compared case-insensitively, ignoring surrounding whitespace.
+///
+/// This is used by the [`FromEnv`] implementation of [`bool`].
+///
+/// ```rust
+/// use derive_environment::boolean::{parse, BoolError};
+///
+/// assert_eq!(parse("TRUE"), Ok(true));
+/// assert_eq!(parse(" yes "), Ok(true));
+/// assert_eq!(parse("Enabled"), Ok(true));
+/// assert_eq!(parse("off"), Ok(false));
+/// assert_eq!(parse("0"), Ok(false));
+/// assert_eq!(parse(""), Err(BoolError(String::new())));
+/// assert_eq!(parse("2"), Err(BoolError(String::from("2"))));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `s` is not one of the values above.
+pub fn parse(s: &str) -> std::result::Result<bool, BoolError> {
+	let value = s.trim();
+	let matches = |names: &[&str]| names.iter().any(|name| name.eq_ignore_ascii_case(value));
+
+	if matches(TRUTHY) {
+		Ok(true)
+	} else if matches(FALSY) {
+		Ok(false)
+	} else {
+		Err(BoolError(s.to_string()))
+	}
+}
+
+/// Like [`parse`], but a variable which is set to an empty value is `true`.
+///
+/// This is used by fields marked `#[env(flag)]`,
+/// so that `DEBUG=` enables a flag just like `DEBUG=1` does.
+///
+/// ```rust
+/// use derive_environment::FromEnv;
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     #[env(flag)]
+///     debug: bool,
+///     verbose: bool,
+/// }
+///
+/// let mut config = Config::default();
+/// config.with_env_from(&[("APP_DEBUG", ""), ("APP_VERBOSE", "on")], "APP").unwrap();
+/// assert!(config.debug);
+/// assert!(config.verbose);
+///
+/// // Without `#[env(flag)]`, an empty value is an error.
+/// assert!(config.with_env_from(&[("APP_VERBOSE", "")], "APP").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if `s` is neither empty nor one of the values accepted by [`parse`].
+pub fn parse_flag(s: &str) -> std::result::Result<bool, BoolError> {
+	if s.trim().is_empty() {
+		Ok(true)
+	} else {
+		parse(s)
+	}
+}
+
+impl FromEnv for bool {
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+		crate::__private::parse_with(self, source, var, parse)
+	}
+}
@@ -0,0 +1,328 @@
+//! Reading variables from `.env` files, without modifying the process environment.
+
+use crate::EnvSource;
+use std::{ffi::OsString, fs, io, path::Path, path::PathBuf, str::FromStr};
+
+/// Errors generated when reading a `.env` file.
+#[derive(Debug, thiserror::Error)]
+pub enum DotEnvError {
+	/// The file could not be read.
+	#[error("failed to read {}", path.display())]
+	Io {
+		/// The file which was being read.
+		path: PathBuf,
+		/// The error returned when reading it.
+		source: io::Error,
+	},
+	/// A line was not a comment, and did not assign a variable.
+	#[error("line {line}: expected `NAME=value`")]
+	ExpectedAssignment {
+		/// The line number, starting from 1.
+		line: usize,
+	},
+	/// A variable's name contained something other than letters, digits, `_`, `.` and `-`,
+	/// or started with a digit.
+	#[error("line {line}: invalid variable name {name:?}")]
+	InvalidName {
+		/// The line number, starting from 1.
+		line: usize,
+		/// The name, as written.
+		name: String,
+	},
+	/// A quoted value was never closed.
+	#[error("line {line}: missing closing {quote}")]
+	Unterminated {
+		/// The line number of the opening quote, starting from 1.
+		line: usize,
+		/// The quote which was opened.
+		quote: char,
+	},
+	/// Something other than a comment followed a quoted value.
+	#[error("line {line}: unexpected {found:?} after closing quote")]
+	TrailingCharacters {
+		/// The line number, starting from 1.
+		line: usize,
+		/// The rest of the line.
+		found: String,
+	},
+}
+
+impl DotEnvError {
+	/// Returns the line number the error occurred on, unless the file could not be read at all.
+	pub fn line(&self) -> Option<usize> {
+		match self {
+			DotEnvError::Io { .. } => None,
+			DotEnvError::ExpectedAssignment { line }
+			| DotEnvError::InvalidName { line, .. }
+			| DotEnvError::Unterminated { line, .. }
+			| DotEnvError::TrailingCharacters { line, .. } => Some(*line),
+		}
+	}
+}
+
+/// Variables parsed from a `.env` file.
+///
+/// This is an [`EnvSource`], so it can be passed to [`FromEnv::with_env_from`](crate::FromEnv::with_env_from)
+/// or layered over the process environment using [`EnvSource::or`].
+/// Unlike most dotenv loaders, the process environment is never modified.
+///
+/// Each line assigns a variable using `NAME=value`, optionally preceded by `export`.
+/// Blank lines and lines starting with `#` are ignored.
+///
+/// - Unquoted values end at the end of the line, or at a `#` preceded by whitespace. Surrounding whitespace is removed.
+/// - Single-quoted values are read exactly as written.
+/// - Double-quoted values may contain the escapes `\n`, `\r`, `\t`, `\\`, `\"`, `\'` and `\$`.
+///   Any other backslash is kept as written.
+///
+/// Quoted values may span several lines, and may be followed by a comment.
+/// If a variable is assigned more than once, the last assignment wins.
+///
+/// ```rust
+/// use derive_environment::{dotenv::DotEnv, FromEnv};
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     name: String,
+///     greeting: String,
+///     key: String,
+///     port: u16,
+/// }
+///
+/// let source: DotEnv = r#"
+/// ## Local development settings
+/// export APP_NAME=demo # the name shown in logs
+/// APP_GREETING="Hello,\n\"world\""
+/// APP_KEY='-----BEGIN KEY-----
+/// abc\n123
+/// -----END KEY-----'
+/// APP_PORT = 8080
+/// "#.parse().unwrap();
+///
+/// let mut config = Config::default();
+/// config.with_env_from(&source, "APP").unwrap();
+///
+/// assert_eq!(config.name, "demo");
+/// assert_eq!(config.greeting, "Hello,\n\"world\"");
+/// assert_eq!(config.key, "-----BEGIN KEY-----\nabc\\n123\n-----END KEY-----");
+/// assert_eq!(config.port, 8080);
+/// ```
+///
+/// A value which is only a comment is empty, but a `#` directly after `=` begins the value:
+///
+/// ```rust
+/// use derive_environment::dotenv::DotEnv;
+///
+/// let source: DotEnv = "A= # not set yet\nB=#ffffff\n".parse().unwrap();
+/// assert_eq!(source.get("A"), Some(""));
+/// assert_eq!(source.get("B"), Some("#ffffff"));
+/// ```
+///
+/// Errors give the line they occurred on:
+///
+/// ```rust
+/// use derive_environment::dotenv::{DotEnv, DotEnvError};
+///
+/// let error = "A=1\nB=\"unclosed\n\nC=3\n".parse::<DotEnv>().unwrap_err();
+/// assert_eq!(error.line(), Some(2));
+/// assert_eq!(error.to_string(), "line 2: missing closing \"");
+///
+/// let error = "A=1\n2B=2\n".parse::<DotEnv>().unwrap_err();
+/// assert_eq!(error.to_string(), "line 2: invalid variable name \"2B\"");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DotEnv {
+	vars: Vec<(String, String)>,
+}
+
+impl DotEnv {
+	/// Reads and parses the file at `path`.
+	///
+	/// # Errors
+	///
+	/// Throws an error if the file could not be read, or contains a line which could not be parsed.
+	pub fn from_path(path: impl AsRef<Path>) -> Result<Self, DotEnvError> {
+		let path = path.as_ref();
+		fs::read_to_string(path)
+			.map_err(|source| DotEnvError::Io {
+				path: path.to_path_buf(),
+				source,
+			})?
+			.parse()
+	}
+
+	/// Returns the value of `name`, if it was assigned.
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.vars
+			.iter()
+			.find(|(var, _)| var == name)
+			.map(|(_, value)| value.as_str())
+	}
+
+	/// Iterates over each variable and its value, in the order they were first assigned.
+	pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.vars
+			.iter()
+			.map(|(var, value)| (var.as_str(), value.as_str()))
+	}
+
+	fn set(&mut self, name: String, value: String) {
+		match self.vars.iter_mut().find(|(var, _)| *var == name) {
+			Some((_, existing)) => *existing = value,
+			None => self.vars.push((name, value)),
+		}
+	}
+}
+
+impl FromStr for DotEnv {
+	type Err = DotEnvError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.replace("\r\n", "\n");
+		let mut parser = Parser { rest: &s, line: 1 };
+		let mut env = DotEnv::default();
+
+		while let Some((name, value)) = parser.assignment()? {
+			env.set(name, value);
+		}
+
+		Ok(env)
+	}
+}
+
+impl EnvSource for DotEnv {
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		self.get(key).map(OsString::from)
+	}
+
+	fn keys(&self) -> Vec<String> {
+		self.vars.iter().map(|(var, _)| var.clone()).collect()
+	}
+}
+
+/// Reads assignments from the start of `rest`, which begins on line `line`.
+struct Parser<'a> {
+	rest: &'a str,
+	line: usize,
+}
+
+impl Parser<'_> {
+	/// Parses the next assignment, skipping blank lines and comments.
+	/// Returns `None` once the end of the file is reached.
+	fn assignment(&mut self) -> Result<Option<(String, String)>, DotEnvError> {
+		loop {
+			let (line, rest) = self.rest.split_once('\n').unwrap_or((self.rest, ""));
+			let trimmed = line.trim();
+			if !trimmed.is_empty() && !trimmed.starts_with('#') {
+				break;
+			}
+			if self.rest.is_empty() {
+				return Ok(None);
+			}
+			self.rest = rest;
+			self.line += 1;
+		}
+
+		let line = self.line;
+		let (text, _) = self.rest.split_once('\n').unwrap_or((self.rest, ""));
+		let Some((name, _)) = text.split_once('=') else {
+			return Err(DotEnvError::ExpectedAssignment { line });
+		};
+
+		let name = name.trim();
+		let name = name
+			.strip_prefix("export")
+			.filter(|rest| rest.starts_with([' ', '\t']))
+			.map_or(name, str::trim_start);
+		let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+			&& name
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'));
+		if !valid {
+			return Err(DotEnvError::InvalidName {
+				line,
+				name: name.to_string(),
+			});
+		}
+		let name = name.to_string();
+
+		self.rest = &self.rest[self.rest.find('=').unwrap_or_default() + 1..];
+		let start = self.rest.trim_start_matches([' ', '\t']);
+
+		let value = match start.chars().next() {
+			Some(quote @ ('\'' | '"')) => {
+				self.rest = &start[1..];
+				let value = self.quoted(quote, line)?;
+				self.end_of_line()?;
+				value
+			}
+			_ => {
+				let (text, rest) = self.rest.split_once('\n').unwrap_or((self.rest, ""));
+				// A comment must be separated from the value, so that `#` may appear within values.
+				// This is checked before trimming, so `A= # comment` leaves `A` empty.
+				let end = text
+					.match_indices('#')
+					.find(|(i, _)| text[..*i].ends_with([' ', '\t']))
+					.map_or(text.len(), |(i, _)| i);
+				self.rest = rest;
+				self.line += 1;
+				text[..end].trim().to_string()
+			}
+		};
+
+		Ok(Some((name, value)))
+	}
+
+	/// Reads a value up to the closing `quote`, which was opened on line `line`.
+	fn quoted(&mut self, quote: char, line: usize) -> Result<String, DotEnvError> {
+		let mut value = String::new();
+		let mut chars = self.rest.char_indices();
+
+		while let Some((i, c)) = chars.next() {
+			match c {
+				c if c == quote => {
+					self.rest = &self.rest[i + 1..];
+					return Ok(value);
+				}
+				'\\' if quote == '"' => {
+					let escaped = chars.clone().next().and_then(|(_, next)| match next {
+						'n' => Some('\n'),
+						'r' => Some('\r'),
+						't' => Some('\t'),
+						'\\' | '"' | '\'' | '$' => Some(next),
+						_ => None,
+					});
+					match escaped {
+						Some(escaped) => {
+							chars.next();
+							value.push(escaped);
+						}
+						None => value.push('\\'),
+					}
+				}
+				'\n' => {
+					self.line += 1;
+					value.push('\n');
+				}
+				c => value.push(c),
+			}
+		}
+
+		Err(DotEnvError::Unterminated { line, quote })
+	}
+
+	/// Skips the rest of the line after a quoted value, which may only contain a comment.
+	fn end_of_line(&mut self) -> Result<(), DotEnvError> {
+		let (text, rest) = self.rest.split_once('\n').unwrap_or((self.rest, ""));
+		let found = text.trim();
+		if !found.is_empty() && !found.starts_with('#') {
+			return Err(DotEnvError::TrailingCharacters {
+				line: self.line,
+				found: found.to_string(),
+			});
+		}
+
+		self.rest = rest;
+		self.line += 1;
+		Ok(())
+	}
+}
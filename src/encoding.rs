@@ -1,22 +1,24 @@
-use crate::FromEnv;
+use crate::{EnvSchema, EnvSource, EnvVar, FromEnv};
 use encoding_rs::Encoding;
 use std::env;
 
 impl FromEnv for &'static Encoding {
-	fn with_env(&mut self, s: &str) -> crate::Result<bool> {
-		match env::var(s) {
+	fn with_env_from(&mut self, source: &dyn EnvSource, s: &str) -> crate::Result<bool> {
+		match source.var(s) {
 			Ok(var) => {
-				*self =
-					Encoding::for_label(var.as_bytes()).ok_or(crate::FromEnvError::ParseError(
-						s.to_string(),
-						String::from("Unrecognized encoding"),
-					))?;
+				*self = Encoding::for_label(var.as_bytes()).ok_or_else(|| {
+					crate::FromEnvError::parse_error(s, &var, "Unrecognized encoding")
+				})?;
 				Ok(true)
 			}
 			Err(env::VarError::NotPresent) => Ok(false),
-			Err(env::VarError::NotUnicode(os)) => {
-				Err(crate::FromEnvError::NotUnicode(s.to_string(), os))
-			}
+			Err(env::VarError::NotUnicode(os)) => Err(crate::FromEnvError::not_unicode(s, os)),
 		}
 	}
 }
+
+impl EnvSchema for &'static Encoding {
+	fn env_schema(prefix: &str) -> Vec<EnvVar> {
+		vec![EnvVar::leaf::<Self>(prefix)]
+	}
+}
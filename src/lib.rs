@@ -2,159 +2,894 @@
 #![warn(missing_docs)]
 
 pub use derive_environment_macros::FromEnv;
-use std::{ffi::OsString, path::PathBuf, str::FromStr};
+use schema::ValueKind;
+pub use schema::{EnvSchema, EnvVar};
+pub use source::{EnvSource, ProcessEnv};
+use std::{
+	ffi::OsString,
+	io,
+	net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+	num::{
+		NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+		NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+	},
+	path::PathBuf,
+};
 
+#[doc(hidden)]
+pub mod __private;
+pub mod boolean;
+pub mod dotenv;
 #[cfg(feature = "encoding_rs")]
 mod encoding;
+pub mod map;
+pub mod schema;
+pub mod source;
+pub mod units;
+pub mod vec;
+mod wrapper;
 
 /// Errors generated when populating a structure from the environment.
 ///
-/// A missing environment variable is *not* considered an Error.
-#[derive(Clone, Debug, thiserror::Error)]
+/// A missing environment variable is *not* considered an Error,
+/// unless its field was marked `#[env(required)]`.
+///
+/// Every error records the [`FieldPath`] leading to the value which caused it.
+///
+/// ```rust
+/// use derive_environment::{FromEnv, PathSegment};
+///
+/// #[derive(Default, FromEnv)]
+/// struct Tls {
+///     port: u16,
+/// }
+///
+/// #[derive(Default, FromEnv)]
+/// struct Server {
+///     tls: Tls,
+/// }
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     servers: Vec<Server>,
+/// }
+///
+/// let source = [("APP_SERVERS_0_TLS_PORT", "443"), ("APP_SERVERS_1_TLS_PORT", "https")];
+///
+/// let mut config = Config::default();
+/// let error = config.with_env_from(&source, "APP").unwrap_err();
+///
+/// assert_eq!(error.var(), "APP_SERVERS_1_TLS_PORT");
+/// assert_eq!(error.path().to_string(), "servers[1].tls.port");
+/// assert_eq!(
+///     error.path().segments()[0],
+///     PathSegment::Field { parent: "Config", name: "servers" },
+/// );
+/// ```
+#[derive(Debug, thiserror::Error)]
 pub enum FromEnvError {
 	/// Thrown when an environment variable was found, but was not valid unicode.
-	#[error("environment variable {0} was not valid unicode: {1:?}")]
-	NotUnicode(String, OsString),
+	#[error("environment variable {var}{} was not valid unicode: {value:?}", field_context(.path))]
+	NotUnicode {
+		/// The variable which was not unicode.
+		var: String,
+		/// The variable's value.
+		value: OsString,
+		/// The fields leading to the value which could not be read.
+		path: FieldPath,
+	},
 	/// Thrown when a unicode environment variable was found, but it could not be parsed.
-	#[error("failed to parse environment variable {0}: {1}")]
-	ParseError(String, String),
+	///
+	/// The error returned by the parser is kept as the [`source`](std::error::Error::source) of this error,
+	/// so it may be downcast to its original type.
+	#[error("failed to parse environment variable {var}{}", parse_context(.path, .value))]
+	ParseError {
+		/// The variable which could not be parsed.
+		var: String,
+		/// The variable's value, or `None` if it was redacted.
+		value: Option<String>,
+		/// The fields leading to the value which could not be parsed.
+		path: FieldPath,
+		/// The error returned by the parser.
+		source: Box<dyn std::error::Error + Send + Sync>,
+	},
+	/// Thrown when a required environment variable, or every variable of a required structure, was absent.
+	#[error("required environment variable {var}{} was not set", field_context(.path))]
+	Missing {
+		/// The variable which was absent.
+		var: String,
+		/// The fields leading to the value which was absent.
+		path: FieldPath,
+	},
+	/// Thrown when a vector requiring contiguous indices has a gap, with the first missing index.
+	#[error("index {index} of environment variable {var}{} was not set, but later indices were", field_context(.path))]
+	IndexGap {
+		/// The prefix of the vector's variables.
+		var: String,
+		/// The first index which was absent.
+		index: usize,
+		/// The fields leading to the vector.
+		path: FieldPath,
+	},
+	/// Thrown when a file named by a `_FILE` variable (see `#[env(file)]`) could not be read.
+	#[error("failed to read {} named by environment variable {var}{}", file.display(), field_context(.path))]
+	Io {
+		/// The variable naming the file.
+		var: String,
+		/// The file which could not be read.
+		file: PathBuf,
+		/// The fields leading to the value which could not be read.
+		path: FieldPath,
+		/// The error returned while reading the file.
+		source: io::Error,
+	},
+}
+
+impl FromEnvError {
+	/// Creates a [`FromEnvError::NotUnicode`] for `var`, which held `value`.
+	pub fn not_unicode(var: impl Into<String>, value: OsString) -> Self {
+		Self::NotUnicode {
+			var: var.into(),
+			value,
+			path: FieldPath::default(),
+		}
+	}
+
+	/// Creates a [`FromEnvError::ParseError`] for `var`, which held `value` when `source` was thrown while parsing it.
+	///
+	/// ```rust
+	/// use derive_environment::FromEnvError;
+	/// use std::{error::Error, num::ParseIntError};
+	///
+	/// let source = "eighty".parse::<u16>().unwrap_err();
+	/// let error = FromEnvError::parse_error("PORT", "eighty", source);
+	///
+	/// assert_eq!(error.to_string(), r#"failed to parse environment variable PORT from "eighty""#);
+	/// assert!(error.source().unwrap().is::<ParseIntError>());
+	/// ```
+	pub fn parse_error(
+		var: impl Into<String>,
+		value: impl Into<String>,
+		source: impl Into<Box<dyn std::error::Error + Send + Sync>>,
+	) -> Self {
+		Self::ParseError {
+			var: var.into(),
+			value: Some(value.into()),
+			path: FieldPath::default(),
+			source: source.into(),
+		}
+	}
+
+	/// Creates a [`FromEnvError::Missing`] for `var`.
+	pub fn missing(var: impl Into<String>) -> Self {
+		Self::Missing {
+			var: var.into(),
+			path: FieldPath::default(),
+		}
+	}
+
+	/// Creates a [`FromEnvError::IndexGap`] for the vector read from `var`, which is missing `index`.
+	pub fn index_gap(var: impl Into<String>, index: usize) -> Self {
+		Self::IndexGap {
+			var: var.into(),
+			index,
+			path: FieldPath::default(),
+		}
+	}
+
+	/// Creates a [`FromEnvError::Io`] for the file named by `var`, which could not be read.
+	pub fn io(var: impl Into<String>, file: impl Into<PathBuf>, source: io::Error) -> Self {
+		Self::Io {
+			var: var.into(),
+			file: file.into(),
+			path: FieldPath::default(),
+			source,
+		}
+	}
+
+	/// Returns the variable which caused this error.
+	pub fn var(&self) -> &str {
+		match self {
+			Self::NotUnicode { var, .. }
+			| Self::ParseError { var, .. }
+			| Self::Missing { var, .. }
+			| Self::IndexGap { var, .. }
+			| Self::Io { var, .. } => var,
+		}
+	}
+
+	/// Returns the fields leading to the value which caused this error.
+	pub fn path(&self) -> &FieldPath {
+		match self {
+			Self::NotUnicode { path, .. }
+			| Self::ParseError { path, .. }
+			| Self::Missing { path, .. }
+			| Self::IndexGap { path, .. }
+			| Self::Io { path, .. } => path,
+		}
+	}
+
+	/// Removes the value of the variable from this error, so that it cannot be displayed or logged.
+	///
+	/// The parser's error often quotes the value too, so the [`source`](std::error::Error::source)
+	/// of a [`FromEnvError::ParseError`] is replaced by [`RedactedError`].
+	/// Errors from fields marked `#[env(redact)]` are redacted automatically.
+	///
+	/// ```rust
+	/// use derive_environment::{FromEnv, FromEnvError};
+	///
+	/// #[derive(Default, FromEnv)]
+	/// struct Config {
+	///     #[env(redact)]
+	///     pin: u32,
+	/// }
+	///
+	/// let mut config = Config::default();
+	/// let error = config.with_env_from(&[("APP_PIN", "12a4")], "APP").unwrap_err();
+	///
+	/// assert!(matches!(&error, FromEnvError::ParseError { value: None, .. }));
+	/// assert_eq!(error.to_string(), "failed to parse environment variable APP_PIN (field `pin`, value redacted)");
+	/// ```
+	///
+	/// Nothing in the error's causes mentions the value either:
+	///
+	/// ```rust
+	/// use derive_environment::FromEnv;
+	/// use std::time::Duration;
+	///
+	/// #[derive(Default, FromEnv)]
+	/// enum Mode {
+	///     #[default]
+	///     Fast,
+	///     Safe,
+	/// }
+	///
+	/// #[derive(Default, FromEnv)]
+	/// struct Config {
+	///     #[env(redact)]
+	///     flag: bool,
+	///     #[env(redact)]
+	///     timeout: Duration,
+	///     #[env(redact)]
+	///     mode: Mode,
+	///     #[env(redact, delimiter = ',')]
+	///     pins: Vec<u32>,
+	/// }
+	///
+	/// let source = [
+	///     ("APP_FLAG", "hunter2"),
+	///     ("APP_TIMEOUT", "hunter2"),
+	///     ("APP_MODE", "hunter2"),
+	///     ("APP_PINS", "1,hunter2"),
+	/// ];
+	///
+	/// let mut config = Config::default();
+	/// let errors = config.with_env_all_from(&source, "APP").unwrap_err();
+	///
+	/// assert_eq!(errors.len(), 4);
+	/// assert!(!errors.to_string().contains("hunter2"));
+	/// assert!(!format!("{errors:?}").contains("hunter2"));
+	/// ```
+	pub fn redact(&mut self) {
+		if let Self::ParseError { value, source, .. } = self {
+			*value = None;
+			*source = Box::new(RedactedError);
+		}
+	}
+
+	/// Records that this error was thrown while loading `segment`.
+	pub(crate) fn within(&mut self, segment: PathSegment) {
+		let path = match self {
+			Self::NotUnicode { path, .. }
+			| Self::ParseError { path, .. }
+			| Self::Missing { path, .. }
+			| Self::IndexGap { path, .. }
+			| Self::Io { path, .. } => path,
+		};
+		path.segments.insert(0, segment);
+	}
 }
 
+/// Replaces the parser's error in a redacted [`FromEnvError::ParseError`], since it may contain the value.
+#[derive(Debug, thiserror::Error)]
+#[error("details redacted")]
+pub struct RedactedError;
+
+/// Describes the field of an error, if it has one.
+fn field_context(path: &FieldPath) -> String {
+	if path.is_empty() {
+		String::new()
+	} else {
+		format!(" (field `{path}`)")
+	}
+}
+
+/// Describes the field and value of a [`FromEnvError::ParseError`].
+fn parse_context(path: &FieldPath, value: &Option<String>) -> String {
+	match (path.is_empty(), value) {
+		(true, Some(value)) => format!(" from {value:?}"),
+		(true, None) => String::from(" (value redacted)"),
+		(false, Some(value)) => format!(" (field `{path}`) from {value:?}"),
+		(false, None) => format!(" (field `{path}`, value redacted)"),
+	}
+}
+
+/// The fields, indices and keys leading from the structure being loaded to a value within it,
+/// such as `servers[3].tls.cert`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FieldPath {
+	segments: Vec<PathSegment>,
+}
+
+impl FieldPath {
+	/// Returns each step of the path, outermost first.
+	pub fn segments(&self) -> &[PathSegment] {
+		&self.segments
+	}
+
+	/// Returns `true` if the path has no segments, meaning the value was loaded directly.
+	pub fn is_empty(&self) -> bool {
+		self.segments.is_empty()
+	}
+}
+
+impl std::fmt::Display for FieldPath {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (i, segment) in self.segments.iter().enumerate() {
+			match segment {
+				PathSegment::Field { name, .. } if i == 0 => f.write_str(name)?,
+				PathSegment::Field { name, .. } => write!(f, ".{name}")?,
+				PathSegment::Index(index) => write!(f, "[{index}]")?,
+				PathSegment::Key(key) => write!(f, "[{key:?}]")?,
+				PathSegment::AnyIndex => f.write_str("[<n>]")?,
+				PathSegment::AnyKey => f.write_str("[<key>]")?,
+			}
+		}
+		Ok(())
+	}
+}
+
+/// A step in a [`FieldPath`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PathSegment {
+	/// A named or positional field of a structure.
+	Field {
+		/// The structure containing the field, or `Enum::Variant` for fields of an enum.
+		parent: &'static str,
+		/// The field's name, or its position in a tuple.
+		name: &'static str,
+	},
+	/// An element of a vector.
+	Index(usize),
+	/// An entry of a map, identified by the key taken from its variable's name.
+	Key(String),
+	/// Every element of a vector, as listed by [`EnvSchema`].
+	AnyIndex,
+	/// Every entry of a map, as listed by [`EnvSchema`].
+	AnyKey,
+}
+
+/// Every error encountered by [`FromEnv::with_env_all`].
+///
+/// Each error is paired with the name of the variable that caused it.
+#[derive(Debug, Default)]
+pub struct FromEnvErrors {
+	errors: Vec<(String, FromEnvError)>,
+}
+
+impl FromEnvErrors {
+	/// Records an error caused by `var`.
+	pub fn push(&mut self, var: impl Into<String>, error: FromEnvError) {
+		self.errors.push((var.into(), error));
+	}
+
+	/// Returns the number of errors collected.
+	pub fn len(&self) -> usize {
+		self.errors.len()
+	}
+
+	/// Returns `true` if no errors have been collected.
+	pub fn is_empty(&self) -> bool {
+		self.errors.is_empty()
+	}
+
+	/// Iterates over each variable name and the error it caused.
+	pub fn iter(&self) -> std::slice::Iter<'_, (String, FromEnvError)> {
+		self.errors.iter()
+	}
+}
+
+impl IntoIterator for FromEnvErrors {
+	type Item = (String, FromEnvError);
+	type IntoIter = std::vec::IntoIter<(String, FromEnvError)>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.errors.into_iter()
+	}
+}
+
+impl<'a> IntoIterator for &'a FromEnvErrors {
+	type Item = &'a (String, FromEnvError);
+	type IntoIter = std::slice::Iter<'a, (String, FromEnvError)>;
+
+	fn into_iter(self) -> Self::IntoIter {
+		self.errors.iter()
+	}
+}
+
+impl std::fmt::Display for FromEnvErrors {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(
+			f,
+			"{} environment variable(s) could not be used",
+			self.errors.len()
+		)?;
+		for (_, error) in &self.errors {
+			write!(f, "\n- {error}")?;
+
+			// Each error is listed on one line, so its causes are included.
+			let mut source = std::error::Error::source(error);
+			while let Some(error) = source {
+				write!(f, ": {error}")?;
+				source = error.source();
+			}
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for FromEnvErrors {}
+
 /// The result of [`FromEnv::with_env`].
 pub type Result<T> = std::result::Result<T, FromEnvError>;
 
 /// Denotes a type that may be read from an environment variable.
 pub trait FromEnv: Sized {
-	/// Reads and parses an environment variable.
+	/// The prefix used by [`FromEnv::from_env`].
+	///
+	/// When deriving, this is set using `#[env(prefix = "...")]`.
+	/// The default is an empty prefix, which reads each field from a variable named after it.
+	const PREFIX: &'static str = "";
+
+	/// Creates a default value, then populates it from the process environment using [`FromEnv::PREFIX`].
+	///
+	/// # Errors
+	///
+	/// Throws an error if an environment variable could not be read or parsed;
+	fn from_env() -> Result<Self>
+	where
+		Self: Default,
+	{
+		Self::from_env_from(&ProcessEnv)
+	}
+
+	/// Creates a default value, then populates it from `source` using [`FromEnv::PREFIX`].
+	///
+	/// ```rust
+	/// use derive_environment::FromEnv;
+	///
+	/// #[derive(Default, FromEnv)]
+	/// #[env(prefix = "APP", separator = "__")]
+	/// struct Config {
+	///     listen_port: u16,
+	/// }
+	///
+	/// let config = Config::from_env_from(&[("APP__LISTEN_PORT", "8080")]).unwrap();
+	/// assert_eq!(config.listen_port, 8080);
+	/// ```
+	///
+	/// # Errors
+	///
+	/// Throws an error if a variable could not be read or parsed;
+	fn from_env_from(source: &dyn EnvSource) -> Result<Self>
+	where
+		Self: Default,
+	{
+		let mut value = Self::default();
+		value.with_env_from(source, Self::PREFIX)?;
+		Ok(value)
+	}
+
+	/// Reads and parses an environment variable from the process environment.
 	/// Returns `Ok(true)` if an environment variable was found and used, and `Ok(false)` if it was absent.
 	///
 	/// # Errors
 	///
 	/// Throws an error if the environment variable could not be read or parsed;
-	fn with_env(&mut self, var: &str) -> Result<bool>;
-}
+	fn with_env(&mut self, var: &str) -> Result<bool> {
+		self.with_env_from(&ProcessEnv, var)
+	}
 
-/// Helper type for mainting a no-alloc string representation.
-#[derive(Debug)]
-struct DigitContainer {
-	// This could be improved slightly by using `ascii::Char` with `from_u8_unchecked`,
-	// but at the time of writing this requires nightly (and the obvious `unsafe` block).
-	digits: [u8; usize::MAX.ilog10() as usize],
-}
+	/// Reads and parses a variable from `source`.
+	/// Returns `Ok(true)` if a variable was found and used, and `Ok(false)` if it was absent.
+	///
+	/// # Errors
+	///
+	/// Throws an error if the variable could not be read or parsed;
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool>;
 
-impl DigitContainer {
-	fn new() -> Self {
-		let mut digits: [u8; usize::MAX.ilog10() as usize] = [0; usize::MAX.ilog10() as usize];
-		digits[0] = 1;
-		Self { digits }
+	/// Like [`FromEnv::with_env`], but does not stop at the first error.
+	///
+	/// Every variable is visited, and all valid ones are applied even if others fail.
+	///
+	/// # Errors
+	///
+	/// Returns every error encountered if any variable could not be read or parsed.
+	fn with_env_all(&mut self, var: &str) -> std::result::Result<bool, FromEnvErrors> {
+		self.with_env_all_from(&ProcessEnv, var)
 	}
 
-	fn next(&mut self, s: &mut String) {
-		let mut digit_iter = self.digits.into_iter().rev();
+	/// Like [`FromEnv::with_env_from`], but does not stop at the first error.
+	///
+	/// ```rust
+	/// use derive_environment::FromEnv;
+	///
+	/// #[derive(Default, FromEnv)]
+	/// struct Config {
+	///     port: u16,
+	///     name: String,
+	///     workers: u8,
+	/// }
+	///
+	/// let vars = [("APP_PORT", "eighty"), ("APP_NAME", "server"), ("APP_WORKERS", "-1")];
+	///
+	/// let mut config = Config::default();
+	/// let errors = config.with_env_all_from(&vars, "APP").unwrap_err();
+	///
+	/// assert_eq!(config.name, "server");
+	/// let failed: Vec<_> = errors.iter().map(|(var, _)| var.as_str()).collect();
+	/// assert_eq!(failed, ["APP_PORT", "APP_WORKERS"]);
+	/// ```
+	///
+	/// # Errors
+	///
+	/// Returns every error encountered if any variable could not be read or parsed.
+	fn with_env_all_from(
+		&mut self,
+		source: &dyn EnvSource,
+		var: &str,
+	) -> std::result::Result<bool, FromEnvErrors> {
+		let mut errors = FromEnvErrors::default();
+		let found = self.collect_env_from(source, var, &mut errors);
 
-		while let Some(digit) = digit_iter.next() {
-			if digit != 0 {
-				s.push((digit + b'0').into());
+		if errors.is_empty() {
+			Ok(found)
+		} else {
+			Err(errors)
+		}
+	}
 
-				for digit in digit_iter {
-					s.push((digit + b'0').into());
-				}
-				break;
+	/// Reads and parses a variable from `source`, pushing any errors to `errors` instead of returning early.
+	/// Returns `true` if a variable was found and used.
+	///
+	/// The default implementation defers to [`FromEnv::with_env_from`];
+	/// types containing several variables should override this to visit all of them.
+	fn collect_env_from(
+		&mut self,
+		source: &dyn EnvSource,
+		var: &str,
+		errors: &mut FromEnvErrors,
+	) -> bool {
+		match self.with_env_from(source, var) {
+			Ok(found) => found,
+			Err(error) => {
+				errors.push(var, error);
+				false
 			}
 		}
+	}
+}
 
-		for digit in &mut self.digits {
-			*digit += 1;
-			if *digit != 10 {
-				break;
-			} else {
-				*digit = 0
+/// Decides whether an error aborts loading, or is collected so that loading may continue.
+///
+/// This allows container implementations to share one body
+/// between [`FromEnv::with_env_from`] and [`FromEnv::collect_env_from`].
+pub(crate) trait Sink {
+	/// Loads `value` from `var`, returning whether or not it was found.
+	fn load<T: FromEnv>(
+		&mut self,
+		value: &mut T,
+		source: &dyn EnvSource,
+		var: &str,
+	) -> Result<bool>;
+
+	/// Reports an error caused by `var`.
+	fn report(&mut self, var: &str, error: FromEnvError) -> Result<()>;
+
+	/// Returns the number of errors collected so far.
+	fn error_count(&self) -> usize;
+
+	/// Runs `load`, recording `segment` in the path of any errors it throws.
+	fn scope(
+		&mut self,
+		segment: PathSegment,
+		load: impl FnOnce(&mut Self) -> Result<bool>,
+	) -> Result<bool>;
+
+	/// Loads `value` from `var` like [`Sink::load`], but ignores its errors if none of its variables were present.
+	///
+	/// This probes for optional values, such as the next element of a vector,
+	/// which are absent rather than missing their required fields when nothing was set.
+	fn load_present<T: FromEnv>(
+		&mut self,
+		value: &mut T,
+		source: &dyn EnvSource,
+		var: &str,
+	) -> Result<bool> {
+		let recorder = source::Recorder::new(source);
+		let mut errors = FromEnvErrors::default();
+		let found = value.collect_env_from(&recorder, var, &mut errors);
+
+		if !recorder.into_read().is_empty() {
+			for (var, error) in errors {
+				self.report(&var, error)?;
 			}
 		}
+		Ok(found)
 	}
 }
 
-/// Automatically implements [`FromEnv`] using the type's [`FromStr`] implementation.
+/// A [`Sink`] which returns the first error encountered.
+pub(crate) struct FailFast;
+
+impl Sink for FailFast {
+	fn load<T: FromEnv>(
+		&mut self,
+		value: &mut T,
+		source: &dyn EnvSource,
+		var: &str,
+	) -> Result<bool> {
+		value.with_env_from(source, var)
+	}
+
+	fn report(&mut self, _var: &str, error: FromEnvError) -> Result<()> {
+		Err(error)
+	}
+
+	fn error_count(&self) -> usize {
+		0
+	}
+
+	fn scope(
+		&mut self,
+		segment: PathSegment,
+		load: impl FnOnce(&mut Self) -> Result<bool>,
+	) -> Result<bool> {
+		load(self).map_err(|mut error| {
+			error.within(segment);
+			error
+		})
+	}
+}
+
+impl Sink for FromEnvErrors {
+	fn load<T: FromEnv>(
+		&mut self,
+		value: &mut T,
+		source: &dyn EnvSource,
+		var: &str,
+	) -> Result<bool> {
+		Ok(value.collect_env_from(source, var, self))
+	}
+
+	fn report(&mut self, var: &str, error: FromEnvError) -> Result<()> {
+		self.push(var, error);
+		Ok(())
+	}
+
+	fn error_count(&self) -> usize {
+		self.len()
+	}
+
+	fn scope(
+		&mut self,
+		segment: PathSegment,
+		load: impl FnOnce(&mut Self) -> Result<bool>,
+	) -> Result<bool> {
+		let start = self.len();
+		let result = load(self);
+		for (_, error) in &mut self.errors[start..] {
+			error.within(segment.clone());
+		}
+		result
+	}
+}
+
+/// Automatically implements [`FromEnv`] using the type's [`FromStr`](std::str::FromStr) implementation.
+///
+/// [`EnvSchema`] is also implemented, describing the type as a single variable.
+/// The values the variable accepts may be described by following the type with `=> kind`:
+///
+/// ```rust
+/// use derive_environment::{impl_using_from_str, schema::ValueKind, EnvSchema};
+/// use std::str::FromStr;
+///
+/// #[derive(Default)]
+/// struct Version(String);
+///
+/// impl FromStr for Version {
+///     type Err = String;
+///
+///     fn from_str(s: &str) -> Result<Self, String> {
+///         Ok(Self(s.to_string()))
+///     }
+/// }
+///
+/// impl_using_from_str!(Version => ValueKind::Pattern(String::from(r"^[0-9]+\.[0-9]+$")));
+///
+/// assert_eq!(Version::env_schema("VERSION")[0].kind, ValueKind::Pattern(String::from(r"^[0-9]+\.[0-9]+$")));
+/// ```
 #[macro_export]
 macro_rules! impl_using_from_str {
     ($type:ty) => {
-        impl FromEnv for $type {
-            fn with_env(&mut self, var: &str) -> ::std::result::Result<bool, FromEnvError> {
+        impl_using_from_str!($type => $crate::schema::ValueKind::Any);
+    };
+    ($type:ty => $kind:expr) => {
+        impl $crate::FromEnv for $type {
+            fn with_env_from(&mut self, source: &dyn $crate::EnvSource, var: &str) -> $crate::Result<bool> {
                 use std::env;
 
-            	match ::std::env::var(var) {
+            	match source.var(var) {
             		Ok(s) => {
-                        *self = s.parse().map_err(|msg: <$type as FromStr>::Err| FromEnvError::ParseError(var.to_string(), msg.to_string()))?;
+                        *self = s.parse().map_err(|error: <$type as ::std::str::FromStr>::Err| $crate::FromEnvError::parse_error(var, s, error))?;
                         Ok(true)
                     }
             		Err(env::VarError::NotPresent) => Ok(false),
-            		Err(env::VarError::NotUnicode(s)) => Err(FromEnvError::NotUnicode(var.to_string(), s)),
+            		Err(env::VarError::NotUnicode(s)) => Err($crate::FromEnvError::not_unicode(var, s)),
             	}
             }
         }
+
+        impl $crate::EnvSchema for $type {
+            fn env_schema(prefix: &str) -> ::std::vec::Vec<$crate::EnvVar> {
+                ::std::vec![$crate::EnvVar::leaf_with::<$type>(prefix, $kind)]
+            }
+        }
     };
-    ($($type:ty),+$(,)?) => {
+    ($($type:ty $(=> $kind:expr)?),+$(,)?) => {
 		$(
-			impl_using_from_str!($type);
+			impl_using_from_str!($type $(=> $kind)?);
 		)+
     };
 }
 
-impl_using_from_str! {
-	u8, u16, u32, u64, u128,
-	i8, i16, i32, i64, i128,
-	bool, String, PathBuf,
+/// Describes the values accepted by an integer type, or by the `NonZero` type wrapping it.
+macro_rules! integer {
+	($type:ty) => {
+		ValueKind::Integer {
+			min: <$type>::MIN as i128,
+			max: <$type>::MAX as u128,
+			nonzero: false,
+		}
+	};
+	(nonzero $type:ty) => {
+		ValueKind::Integer {
+			min: if <$type>::MIN == 0 {
+				1
+			} else {
+				<$type>::MIN as i128
+			},
+			max: <$type>::MAX as u128,
+			nonzero: true,
+		}
+	};
 }
 
-impl<T: FromEnv + Default> FromEnv for Option<T> {
-	fn with_env(&mut self, var: &str) -> Result<bool> {
-		let mut contents = T::default();
-
-		let result = contents.with_env(var);
+impl_using_from_str! {
+	u8 => integer!(u8), u16 => integer!(u16), u32 => integer!(u32),
+	u64 => integer!(u64), u128 => integer!(u128), usize => integer!(usize),
+	i8 => integer!(i8), i16 => integer!(i16), i32 => integer!(i32),
+	i64 => integer!(i64), i128 => integer!(i128), isize => integer!(isize),
+	NonZeroU8 => integer!(nonzero u8), NonZeroU16 => integer!(nonzero u16),
+	NonZeroU32 => integer!(nonzero u32), NonZeroU64 => integer!(nonzero u64),
+	NonZeroU128 => integer!(nonzero u128), NonZeroUsize => integer!(nonzero usize),
+	NonZeroI8 => integer!(nonzero i8), NonZeroI16 => integer!(nonzero i16),
+	NonZeroI32 => integer!(nonzero i32), NonZeroI64 => integer!(nonzero i64),
+	NonZeroI128 => integer!(nonzero i128), NonZeroIsize => integer!(nonzero isize),
+	f32 => ValueKind::Float, f64 => ValueKind::Float, char => ValueKind::Char, String,
+	IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6,
+}
 
-		if matches!(result, Ok(true)) {
-			*self = Some(contents);
+/// Reads the variable as-is, so values which are not unicode are accepted.
+impl FromEnv for OsString {
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+		match source.var_os(var) {
+			Some(s) => {
+				*self = s;
+				Ok(true)
+			}
+			None => Ok(false),
 		}
+	}
+}
 
-		result
+/// Reads the variable as-is, so paths which are not unicode are accepted.
+///
+/// ```rust
+/// use derive_environment::FromEnv;
+/// # #[cfg(unix)] {
+/// use std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::PathBuf};
+///
+/// let name = OsStr::from_bytes(b"caf\xe9.txt");
+///
+/// let mut path = PathBuf::new();
+/// path.with_env_from(&[("DATA_PATH", name)], "DATA_PATH").unwrap();
+/// assert_eq!(path, PathBuf::from(name));
+/// # }
+/// ```
+impl FromEnv for PathBuf {
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+		let mut s = OsString::new();
+		let found = s.with_env_from(source, var)?;
+		if found {
+			*self = s.into();
+		}
+		Ok(found)
 	}
 }
 
-impl<T: FromEnv + Default> FromEnv for Vec<T> {
-	fn with_env(&mut self, prefix: &str) -> Result<bool> {
-		// Working environment variable.
-		let mut var = format!("{prefix}_0");
+impl FromEnv for Box<str> {
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+		let mut s = String::new();
+		let found = s.with_env_from(source, var)?;
+		if found {
+			*self = s.into_boxed_str();
+		}
+		Ok(found)
+	}
+}
 
-		// Special-case first element; if this is present, so is the vector.
-		let mut contents = T::default();
-		let mut v = if contents.with_env(&var)? {
-			vec![contents]
-		} else {
-			return Ok(false);
-		};
+/// Loads into the existing value if there is one, so that variables only replace the parts of it they name.
+/// Otherwise, a default value is loaded and kept if any of its variables were found.
+/// If none of them were set, the option is left as it is, even if the value has required fields.
+///
+/// ```rust
+/// use derive_environment::FromEnv;
+///
+/// #[derive(Default, FromEnv)]
+/// struct Tls {
+///     cert: String,
+///     key: String,
+/// }
+///
+/// let mut tls = Some(Tls { cert: String::from("a.pem"), key: String::from("a.key") });
+/// tls.with_env_from(&[("TLS_CERT", "b.pem")], "TLS").unwrap();
+///
+/// let tls = tls.unwrap();
+/// assert_eq!(tls.cert, "b.pem");
+/// assert_eq!(tls.key, "a.key");
+/// ```
+impl<T: FromEnv + Default> FromEnv for Option<T> {
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+		load_option(self, source, var, &mut FailFast)
+	}
 
-		// Counter as a string.
-		// This is done on the stack to avoid allocations.
-		let mut digits = DigitContainer::new();
-
-		loop {
-			// Rebuild var with no allocations.
-			// (This isn't actually realloc-free; the string may overflow if the digit value becomes too large).
-			// Truncate only modifies the "size" field, meaning we keep our allocated memory.
-			var.truncate(prefix.len() + 1);
-			// Then the previous digits are overwritten.
-			digits.next(&mut var);
-
-			let mut contents = T::default();
-			if contents.with_env(&var)? {
-				v.push(contents);
-			} else {
-				break;
-			}
-		}
+	fn collect_env_from(
+		&mut self,
+		source: &dyn EnvSource,
+		var: &str,
+		errors: &mut FromEnvErrors,
+	) -> bool {
+		// Collecting never returns an error.
+		load_option(self, source, var, errors).unwrap_or(false)
+	}
+}
 
-		*self = v;
+fn load_option<T: FromEnv + Default>(
+	option: &mut Option<T>,
+	source: &dyn EnvSource,
+	var: &str,
+	sink: &mut impl Sink,
+) -> Result<bool> {
+	if let Some(contents) = option {
+		return sink.load_present(contents, source, var);
+	}
 
-		Ok(true)
+	let mut contents = T::default();
+	let found = sink.load_present(&mut contents, source, var)?;
+	if found {
+		*option = Some(contents);
 	}
+	Ok(found)
 }
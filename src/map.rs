@@ -0,0 +1,358 @@
+//! Reading maps, by scanning the source for every variable beneath a prefix.
+
+use crate::{
+	__private::separator, schema, source::Recorder, EnvSchema, EnvSource, EnvVar, FailFast,
+	FromEnv, FromEnvError, FromEnvErrors, PathSegment, Result, Sink,
+};
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	error::Error,
+	hash::{BuildHasher, Hash},
+	str::FromStr,
+};
+
+/// Options controlling how a map is read.
+///
+/// The [`FromEnv`] implementations of [`HashMap`] and [`BTreeMap`] use the default options.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MapOptions {
+	/// How each key is converted before it is parsed.
+	pub key_case: KeyCase,
+}
+
+/// Determines how the key taken from a variable's name is converted before it is parsed.
+///
+/// When deriving, this is set using `#[env(key_case = "...")]`.
+///
+/// ```rust
+/// use derive_environment::map::{self, KeyCase, MapOptions};
+/// use std::collections::BTreeMap;
+///
+/// let source = [("LIMITS_ACME_CORP", "5")];
+/// let mut limits = BTreeMap::<String, u32>::new();
+///
+/// let options = MapOptions { key_case: KeyCase::Kebab };
+/// map::with_env_options(&mut limits, &source, "LIMITS", &options).unwrap();
+/// assert_eq!(limits["acme-corp"], 5);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeyCase {
+	/// Uses the key exactly as it appears in the variable's name.
+	#[default]
+	Preserve,
+	/// Converts the key to lowercase.
+	Lower,
+	/// Converts the key to uppercase.
+	Upper,
+	/// Converts the key to lowercase, replacing each `_` with `-`.
+	Kebab,
+}
+
+impl KeyCase {
+	fn apply(self, key: &str) -> String {
+		match self {
+			KeyCase::Preserve => key.to_string(),
+			KeyCase::Lower => key.to_lowercase(),
+			KeyCase::Upper => key.to_uppercase(),
+			KeyCase::Kebab => key.to_lowercase().replace('_', "-"),
+		}
+	}
+}
+
+/// Thrown when a key taken from a variable's name could not be parsed.
+///
+/// This is the [`source`](std::error::Error::source) of the resulting [`FromEnvError::ParseError`].
+#[derive(Debug, thiserror::Error)]
+#[error("invalid key {key:?}")]
+pub struct KeyError {
+	/// The key, after its case was converted.
+	pub key: String,
+	/// The error returned by the key's `FromStr` implementation.
+	pub source: Box<dyn Error + Send + Sync>,
+}
+
+/// A map which may be read from the environment.
+///
+/// Each entry is read from `PREFIX_<KEY>`, where `<KEY>` is parsed using [`FromStr`].
+/// The key is joined to the prefix with `_`, or with the separator of the derived type being loaded.
+pub trait EnvMap {
+	/// The type of the map's keys.
+	type Key: FromStr;
+	/// The type of the map's values.
+	type Value: FromEnv + Default;
+
+	/// Returns the value stored under `key`, if any.
+	fn get_mut(&mut self, key: &Self::Key) -> Option<&mut Self::Value>;
+
+	/// Stores `value` under `key`.
+	fn insert(&mut self, key: Self::Key, value: Self::Value);
+}
+
+impl<K, V, S> EnvMap for HashMap<K, V, S>
+where
+	K: FromStr + Hash + Eq,
+	V: FromEnv + Default,
+	S: BuildHasher,
+{
+	type Key = K;
+	type Value = V;
+
+	fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+		HashMap::get_mut(self, key)
+	}
+
+	fn insert(&mut self, key: K, value: V) {
+		HashMap::insert(self, key, value);
+	}
+}
+
+impl<K, V> EnvMap for BTreeMap<K, V>
+where
+	K: FromStr + Ord,
+	V: FromEnv + Default,
+{
+	type Key = K;
+	type Value = V;
+
+	fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+		BTreeMap::get_mut(self, key)
+	}
+
+	fn insert(&mut self, key: K, value: V) {
+		BTreeMap::insert(self, key, value);
+	}
+}
+
+/// Reads `map` from `source` according to `options`.
+/// Returns `Ok(true)` if any variable was found and used, and `Ok(false)` if they were all absent.
+///
+/// Existing entries are loaded in place, so a variable only replaces the parts of a value it names.
+///
+/// # Errors
+///
+/// Throws an error if a variable could not be read or parsed, or if its key could not be parsed;
+pub fn with_env_options<M>(
+	map: &mut M,
+	source: &dyn EnvSource,
+	prefix: &str,
+	options: &MapOptions,
+) -> Result<bool>
+where
+	M: EnvMap,
+	<M::Key as FromStr>::Err: Into<Box<dyn Error + Send + Sync>>,
+{
+	load(map, source, prefix, options, &mut FailFast)
+}
+
+/// Like [`with_env_options`], but pushes errors to `errors` instead of returning early.
+pub fn collect_env_options<M>(
+	map: &mut M,
+	source: &dyn EnvSource,
+	prefix: &str,
+	options: &MapOptions,
+	errors: &mut FromEnvErrors,
+) -> bool
+where
+	M: EnvMap,
+	<M::Key as FromStr>::Err: Into<Box<dyn Error + Send + Sync>>,
+{
+	// Collecting never returns an error.
+	load(map, source, prefix, options, errors).unwrap_or(false)
+}
+
+/// Reads every `PREFIX_<KEY>`, or `PREFIX_<KEY>_<FIELD>` for nested values.
+///
+/// Keys may contain the separator; each variable is assigned to the shortest key whose value reads it.
+/// This requires a source which can list its variables (see [`EnvSource::keys`]).
+///
+/// ```rust
+/// use derive_environment::FromEnv;
+/// use std::collections::HashMap;
+///
+/// #[derive(Debug, Default, FromEnv)]
+/// struct Tenant {
+///     quota: u32,
+///     name: String,
+/// }
+///
+/// let mut tenants = HashMap::<String, Tenant>::new();
+/// tenants.insert(String::from("ACME"), Tenant { quota: 10, name: String::from("Acme") });
+///
+/// let source = [("TENANTS_ACME_QUOTA", "20"), ("TENANTS_INITECH_CO_NAME", "Initech")];
+/// tenants.with_env_from(&source, "TENANTS").unwrap();
+///
+/// assert_eq!(tenants["ACME"].quota, 20);
+/// assert_eq!(tenants["ACME"].name, "Acme");
+/// assert_eq!(tenants["INITECH_CO"].name, "Initech");
+/// ```
+impl<K, V, S> FromEnv for HashMap<K, V, S>
+where
+	K: FromStr + Hash + Eq,
+	K::Err: Into<Box<dyn Error + Send + Sync>>,
+	V: FromEnv + Default,
+	S: BuildHasher,
+{
+	fn with_env_from(&mut self, source: &dyn EnvSource, prefix: &str) -> Result<bool> {
+		with_env_options(self, source, prefix, &MapOptions::default())
+	}
+
+	fn collect_env_from(
+		&mut self,
+		source: &dyn EnvSource,
+		prefix: &str,
+		errors: &mut FromEnvErrors,
+	) -> bool {
+		collect_env_options(self, source, prefix, &MapOptions::default(), errors)
+	}
+}
+
+impl<K, V> FromEnv for BTreeMap<K, V>
+where
+	K: FromStr + Ord,
+	K::Err: Into<Box<dyn Error + Send + Sync>>,
+	V: FromEnv + Default,
+{
+	fn with_env_from(&mut self, source: &dyn EnvSource, prefix: &str) -> Result<bool> {
+		with_env_options(self, source, prefix, &MapOptions::default())
+	}
+
+	fn collect_env_from(
+		&mut self,
+		source: &dyn EnvSource,
+		prefix: &str,
+		errors: &mut FromEnvErrors,
+	) -> bool {
+		collect_env_options(self, source, prefix, &MapOptions::default(), errors)
+	}
+}
+
+impl<K, V: EnvSchema, S> EnvSchema for HashMap<K, V, S> {
+	fn env_schema(prefix: &str) -> Vec<EnvVar> {
+		schema::within(
+			V::env_schema(&format!("{prefix}{}<key>", separator())),
+			PathSegment::AnyKey,
+		)
+	}
+}
+
+impl<K, V: EnvSchema> EnvSchema for BTreeMap<K, V> {
+	fn env_schema(prefix: &str) -> Vec<EnvVar> {
+		schema::within(
+			V::env_schema(&format!("{prefix}{}<key>", separator())),
+			PathSegment::AnyKey,
+		)
+	}
+}
+
+fn load<M>(
+	map: &mut M,
+	source: &dyn EnvSource,
+	prefix: &str,
+	options: &MapOptions,
+	sink: &mut impl Sink,
+) -> Result<bool>
+where
+	M: EnvMap,
+	<M::Key as FromStr>::Err: Into<Box<dyn Error + Send + Sync>>,
+{
+	let separator = separator();
+	let mut vars: Vec<String> = source
+		.keys()
+		.into_iter()
+		.filter(|var| {
+			var.strip_prefix(prefix)
+				.and_then(|rest| rest.strip_prefix(separator))
+				.is_some_and(|key| !key.is_empty())
+		})
+		.collect();
+	// Sources may list variables in any order.
+	vars.sort();
+	vars.dedup();
+
+	let mut found = false;
+	// Variables which have been read by an entry.
+	let mut claimed = HashSet::new();
+	// Keys which have already been loaded.
+	let mut loaded = HashSet::new();
+
+	for var in &vars {
+		if claimed.contains(var) {
+			continue;
+		}
+
+		let start = prefix.len() + separator.len();
+		let name = &var[start..];
+		// The shortest key's parse error, in case no other key reads this variable.
+		let mut invalid = None;
+
+		// Try each candidate key, shortest first.
+		let ends = name
+			.match_indices(separator)
+			.map(|(end, _)| end)
+			.chain([name.len()]);
+		for (i, end) in ends.enumerate() {
+			let raw_key = &name[..end];
+			if loaded.contains(raw_key) {
+				continue;
+			}
+
+			let key_name = options.key_case.apply(raw_key);
+			let key = match key_name.parse::<M::Key>() {
+				Ok(key) => key,
+				Err(error) => {
+					if i == 0 {
+						invalid = Some(KeyError {
+							key: key_name,
+							source: error.into(),
+						});
+					}
+					continue;
+				}
+			};
+
+			// A key only claims the variable if its value reads it.
+			// Trying a key discards whatever it loaded, including errors such as missing fields.
+			let entry_var = &var[..start + end];
+			let recorder = Recorder::new(source);
+			M::Value::default().collect_env_from(
+				&recorder,
+				entry_var,
+				&mut FromEnvErrors::default(),
+			);
+			let read = recorder.into_read();
+			if !read.contains(var) {
+				continue;
+			}
+
+			let segment = PathSegment::Key(key_name);
+			found |= match map.get_mut(&key) {
+				Some(existing) => {
+					sink.scope(segment, |sink| sink.load(existing, source, entry_var))?
+				}
+				None => {
+					let mut value = M::Value::default();
+					let entry_found =
+						sink.scope(segment, |sink| sink.load(&mut value, source, entry_var))?;
+					if entry_found {
+						map.insert(key, value);
+					}
+					entry_found
+				}
+			};
+
+			loaded.insert(raw_key);
+			claimed.extend(read);
+			break;
+		}
+
+		if !claimed.contains(var) {
+			if let Some(error) = invalid {
+				let value = source.var_os(var).unwrap_or_default();
+				let error = FromEnvError::parse_error(var, value.to_string_lossy(), error);
+				sink.report(var, error)?;
+			}
+		}
+	}
+
+	Ok(found)
+}
@@ -0,0 +1,840 @@
+//! Describing the variables a type reads, so that documentation and tooling can be generated from it.
+
+use crate::{boolean, FieldPath, PathSegment};
+use std::{
+	any::type_name,
+	borrow::Cow,
+	cell::{Cell, RefCell},
+	ffi::OsString,
+	path::PathBuf,
+	rc::Rc,
+	sync::{Arc, Mutex, RwLock},
+};
+
+/// Lists the variables read by a type's [`FromEnv`](crate::FromEnv) implementation.
+///
+/// This is implemented by `#[derive(FromEnv)]`, as well as every type this crate implements `FromEnv` for.
+/// Vectors and maps list their variables as patterns, using `<n>` for each index and `<key>` for each key.
+///
+/// ```rust
+/// use derive_environment::{EnvSchema, FromEnv};
+///
+/// #[derive(Default, FromEnv)]
+/// struct Server {
+///     /// Port to listen on.
+///     #[env(required)]
+///     port: u16,
+/// }
+///
+/// #[derive(FromEnv)]
+/// #[env(default)]
+/// struct Config {
+///     servers: Vec<Server>,
+///     #[env(default = "info")]
+///     log_level: String,
+/// }
+///
+/// let vars = Config::env_schema("APP");
+///
+/// assert_eq!(vars[0].name, "APP_SERVERS_<n>_PORT");
+/// assert_eq!(vars[0].type_name, "u16");
+/// assert_eq!(vars[0].path.to_string(), "servers[<n>].port");
+/// assert_eq!(vars[0].doc.as_deref(), Some("Port to listen on."));
+/// assert!(vars[0].required);
+///
+/// assert_eq!(vars[1].name, "APP_LOG_LEVEL");
+/// assert_eq!(vars[1].default.as_deref(), Some("info"));
+/// assert!(!vars[1].required);
+/// ```
+pub trait EnvSchema {
+	/// Lists each variable read when loading from `prefix`, in the order they are read.
+	fn env_schema(prefix: &str) -> Vec<EnvVar>;
+}
+
+/// A variable, or pattern of variables, read by a type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EnvVar {
+	/// The variable's name, with `<n>` or `<key>` in place of each index or key.
+	pub name: String,
+	/// The Rust type the variable is parsed as.
+	pub type_name: &'static str,
+	/// The fields leading to the value read from the variable.
+	pub path: FieldPath,
+	/// The documentation of the field the variable is read into.
+	pub doc: Option<String>,
+	/// The field's default value, if it was given by `#[env(default = "...")]`.
+	pub default: Option<String>,
+	/// Whether the variable must be set.
+	pub required: bool,
+	/// The values the variable accepts.
+	pub kind: ValueKind,
+	/// Whether the value may instead be read from the file named by `{name}_FILE` (see `#[env(file)]`).
+	pub file: bool,
+}
+
+/// The values a variable accepts, as far as they can be described without parsing them.
+///
+/// This is used by [`json_schema`] to check values before they reach the program.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ValueKind {
+	/// Any value, including values which can't be described more precisely.
+	#[default]
+	Any,
+	/// A boolean, in any spelling accepted by [`boolean::parse`](crate::boolean::parse).
+	Bool,
+	/// Like [`ValueKind::Bool`], but an empty value is also accepted (see [`boolean::parse_flag`](crate::boolean::parse_flag)).
+	Flag,
+	/// An integer between `min` and `max`, inclusive.
+	Integer {
+		/// The smallest value accepted.
+		min: i128,
+		/// The largest value accepted.
+		max: u128,
+		/// Whether zero is rejected.
+		nonzero: bool,
+	},
+	/// A floating point number, including `inf` and `NaN`.
+	Float,
+	/// A single character.
+	Char,
+	/// One of the given names, compared case-insensitively.
+	OneOf(Vec<String>),
+	/// A value matching a regular expression.
+	Pattern(String),
+}
+
+impl EnvVar {
+	/// Describes a single variable named `name`, which is parsed as a `T`.
+	///
+	/// This is all that types read from a single variable need to implement [`EnvSchema`]:
+	///
+	/// ```rust
+	/// use derive_environment::{EnvSchema, EnvVar};
+	///
+	/// struct Color(u8, u8, u8);
+	///
+	/// impl EnvSchema for Color {
+	///     fn env_schema(prefix: &str) -> Vec<EnvVar> {
+	///         vec![EnvVar::leaf::<Self>(prefix)]
+	///     }
+	/// }
+	/// ```
+	pub fn leaf<T: ?Sized>(name: impl Into<String>) -> Self {
+		Self {
+			name: name.into(),
+			type_name: type_name::<T>(),
+			path: FieldPath::default(),
+			doc: None,
+			default: None,
+			required: false,
+			kind: ValueKind::Any,
+			file: false,
+		}
+	}
+
+	/// Like [`EnvVar::leaf`], but describes the values the variable accepts.
+	///
+	/// ```rust
+	/// use derive_environment::{schema::ValueKind, EnvSchema, EnvVar};
+	///
+	/// struct Percentage(u8);
+	///
+	/// impl EnvSchema for Percentage {
+	///     fn env_schema(prefix: &str) -> Vec<EnvVar> {
+	///         let kind = ValueKind::Integer { min: 0, max: 100, nonzero: false };
+	///         vec![EnvVar::leaf_with::<Self>(prefix, kind)]
+	///     }
+	/// }
+	/// ```
+	pub fn leaf_with<T: ?Sized>(name: impl Into<String>, kind: ValueKind) -> Self {
+		Self {
+			kind,
+			..Self::leaf::<T>(name)
+		}
+	}
+
+	/// Returns [`EnvVar::type_name`] without module paths, such as `Vec<String>` rather than `alloc::vec::Vec<alloc::string::String>`.
+	///
+	/// ```rust
+	/// use derive_environment::EnvVar;
+	///
+	/// let var = EnvVar::leaf::<Vec<std::time::Duration>>("TIMEOUTS");
+	/// assert_eq!(var.short_type_name(), "Vec<Duration>");
+	/// ```
+	pub fn short_type_name(&self) -> String {
+		let mut short = String::new();
+		let mut path = String::new();
+
+		for c in self.type_name.chars() {
+			if c.is_alphanumeric() || c == '_' || c == ':' {
+				path.push(c);
+			} else {
+				short.push_str(path.rsplit("::").next().unwrap_or_default());
+				path.clear();
+				short.push(c);
+			}
+		}
+		short.push_str(path.rsplit("::").next().unwrap_or_default());
+		short
+	}
+
+	/// Returns `true` if this is a pattern standing for many variables, rather than a single variable.
+	pub fn is_pattern(&self) -> bool {
+		self.name.contains('<')
+	}
+
+	/// Records that this variable is read within `segment`.
+	pub(crate) fn within(&mut self, segment: PathSegment) {
+		self.path.segments.insert(0, segment);
+	}
+}
+
+/// Records that each of `vars` is read within `segment`.
+pub(crate) fn within(mut vars: Vec<EnvVar>, segment: PathSegment) -> Vec<EnvVar> {
+	for var in &mut vars {
+		var.within(segment.clone());
+	}
+	vars
+}
+
+/// Implements [`EnvSchema`] for types read from a single variable.
+macro_rules! leaf {
+	($($ty:ty => $kind:expr),+ $(,)?) => {$(
+		impl EnvSchema for $ty {
+			fn env_schema(prefix: &str) -> Vec<EnvVar> {
+				vec![EnvVar::leaf_with::<Self>(prefix, $kind)]
+			}
+		}
+	)+};
+}
+
+leaf!(
+	bool => ValueKind::Bool,
+	OsString => ValueKind::Any,
+	PathBuf => ValueKind::Any,
+	Box<str> => ValueKind::Any,
+);
+
+/// Implements [`EnvSchema`] for types which read the same variables as the `T` they contain.
+macro_rules! transparent {
+	($($ty:ty),+ $(,)?) => {$(
+		impl<T: EnvSchema> EnvSchema for $ty {
+			fn env_schema(prefix: &str) -> Vec<EnvVar> {
+				T::env_schema(prefix)
+			}
+		}
+	)+};
+}
+
+transparent!(
+	Option<T>,
+	Box<T>,
+	Rc<T>,
+	Arc<T>,
+	Cell<T>,
+	RefCell<T>,
+	Mutex<T>,
+	RwLock<T>
+);
+
+/// Renders `vars` as a `.env.example` file, commenting each variable with its documentation.
+///
+/// Required variables are left uncommented so that they stand out, while optional variables and patterns are commented out.
+/// Each variable is set to its default, if it has one.
+/// Defaults which [`DotEnv`](crate::dotenv::DotEnv) would not read back unchanged, such as those containing ` #`,
+/// a newline or surrounding whitespace, are written in double quotes.
+///
+/// ```rust
+/// use derive_environment::{schema, EnvSchema, FromEnv};
+///
+/// #[derive(FromEnv)]
+/// #[env(default)]
+/// struct Config {
+///     /// Address of the database.
+///     #[env(required)]
+///     database_url: String,
+///     /// Port to listen on.
+///     #[env(default = "8080")]
+///     port: u16,
+///     hosts: Vec<String>,
+/// }
+///
+/// let example = schema::dotenv_example(&Config::env_schema("APP"));
+/// assert_eq!(example, "\
+/// ## Address of the database.
+/// ## Type: String (required)
+/// APP_DATABASE_URL=
+///
+/// ## Port to listen on.
+/// ## Type: u16
+/// ## APP_PORT=8080
+///
+/// ## Type: String
+/// ## APP_HOSTS_<n>=
+/// ");
+/// ```
+///
+/// Quoted defaults read back as written once uncommented:
+///
+/// ```rust
+/// use derive_environment::{dotenv::DotEnv, schema, EnvSchema, FromEnv};
+///
+/// #[derive(FromEnv)]
+/// #[env(default)]
+/// struct Config {
+///     #[env(required, default = " #1 ")]
+///     tag: String,
+/// }
+///
+/// let example = schema::dotenv_example(&Config::env_schema("APP"));
+/// assert!(example.ends_with("APP_TAG=\" #1 \"\n"));
+///
+/// let source: DotEnv = example.parse().unwrap();
+/// assert_eq!(source.get("APP_TAG"), Some(" #1 "));
+/// ```
+pub fn dotenv_example(vars: &[EnvVar]) -> String {
+	let mut out = String::new();
+
+	for (i, var) in vars.iter().enumerate() {
+		if i > 0 {
+			out.push('\n');
+		}
+		for line in var.doc.iter().flat_map(|doc| doc.lines()) {
+			out.push_str(format!("# {line}").trim_end());
+			out.push('\n');
+		}
+
+		out.push_str("# Type: ");
+		out.push_str(&var.short_type_name());
+		if var.required {
+			out.push_str(" (required)");
+		}
+		out.push('\n');
+		if var.file {
+			out.push_str(&format!(
+				"# May instead be read from the file named by {}_FILE.\n",
+				var.name
+			));
+		}
+
+		if !var.required || var.is_pattern() {
+			out.push_str("# ");
+		}
+		out.push_str(&var.name);
+		out.push('=');
+		out.push_str(&dotenv_value(var.default.as_deref().unwrap_or_default()));
+		out.push('\n');
+	}
+
+	out
+}
+
+/// Quotes `value` for a `.env` file if reading it unquoted would change it.
+fn dotenv_value(value: &str) -> Cow<'_, str> {
+	let plain = value.trim() == value
+		&& !value.starts_with(['\'', '"'])
+		&& !value.contains(['#', '\n', '\r', '\\', '"', '$']);
+	if plain {
+		return Cow::Borrowed(value);
+	}
+
+	let mut quoted = String::from('"');
+	for c in value.chars() {
+		match c {
+			'\n' => quoted.push_str("\\n"),
+			'\r' => quoted.push_str("\\r"),
+			'\t' => quoted.push_str("\\t"),
+			'\\' | '"' | '$' => {
+				quoted.push('\\');
+				quoted.push(c);
+			}
+			c => quoted.push(c),
+		}
+	}
+	quoted.push('"');
+	Cow::Owned(quoted)
+}
+
+/// Renders `vars` as a Markdown table, with columns for each variable's name, type, default, requirement and documentation.
+///
+/// ```rust
+/// use derive_environment::{schema, EnvSchema, FromEnv};
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     /// Port to listen on.
+///     #[env(required)]
+///     port: u16,
+/// }
+///
+/// assert_eq!(schema::markdown_table(&Config::env_schema("APP")), "\
+/// | Variable | Type | Default | Required | Description |
+/// | --- | --- | --- | --- | --- |
+/// | `APP_PORT` | `u16` |  | yes | Port to listen on. |
+/// ");
+/// ```
+pub fn markdown_table(vars: &[EnvVar]) -> String {
+	let mut out = String::from(
+		"| Variable | Type | Default | Required | Description |\n| --- | --- | --- | --- | --- |\n",
+	);
+
+	for var in vars {
+		// Pipes would end the cell early, and each row must stay on one line.
+		let escape = |s: &str| s.replace('|', "\\|").replace('\n', "<br>");
+		let default = match &var.default {
+			Some(default) => format!("`{}`", escape(default)),
+			None => String::new(),
+		};
+
+		out.push_str(&format!(
+			"| `{}` | `{}` | {} | {} | {} |\n",
+			var.name,
+			escape(&var.short_type_name()),
+			default,
+			if var.required { "yes" } else { "no" },
+			escape(var.doc.as_deref().unwrap_or_default()),
+		));
+	}
+
+	out
+}
+
+/// Renders `vars` as a plain-text table, with columns aligned using spaces.
+///
+/// Only the first line of each variable's documentation is included.
+///
+/// ```rust
+/// use derive_environment::{schema, EnvSchema, FromEnv};
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     /// Port to listen on.
+///     #[env(required)]
+///     port: u16,
+///     name: String,
+/// }
+///
+/// assert_eq!(schema::text_table(&Config::env_schema("APP")), "\
+/// VARIABLE  TYPE    DEFAULT  REQUIRED  DESCRIPTION
+/// APP_PORT  u16              yes       Port to listen on.
+/// APP_NAME  String           no
+/// ");
+/// ```
+pub fn text_table(vars: &[EnvVar]) -> String {
+	let header = ["VARIABLE", "TYPE", "DEFAULT", "REQUIRED", "DESCRIPTION"].map(String::from);
+	let rows: Vec<[String; 5]> = vars
+		.iter()
+		.map(|var| {
+			[
+				var.name.clone(),
+				var.short_type_name(),
+				var.default.clone().unwrap_or_default(),
+				String::from(if var.required { "yes" } else { "no" }),
+				var.doc
+					.as_deref()
+					.and_then(|doc| doc.lines().next())
+					.unwrap_or_default()
+					.to_string(),
+			]
+		})
+		.collect();
+
+	let mut widths = header.clone().map(|cell| cell.chars().count());
+	for row in &rows {
+		for (width, cell) in widths.iter_mut().zip(row) {
+			*width = (*width).max(cell.chars().count());
+		}
+	}
+
+	let mut out = String::new();
+	for row in std::iter::once(&header).chain(&rows) {
+		let mut line = String::new();
+		for (cell, width) in row.iter().zip(widths) {
+			line.push_str(&format!("{cell:width$}  "));
+		}
+		out.push_str(line.trim_end());
+		out.push('\n');
+	}
+
+	out
+}
+
+/// Renders `vars` as a [JSON Schema](https://json-schema.org/) describing an object of variables, such as a container's environment.
+///
+/// Each variable's [`ValueKind`] is checked with a `pattern`, so that values written as strings are checked just as the program would parse them.
+/// Booleans and numbers may also be written as JSON booleans and numbers.
+/// Integers are checked against the range of their type either way, so `"99999"` is rejected for a `u16`.
+/// Patterns of variables, such as `APP_HOSTS_<n>`, are described using `patternProperties`.
+/// Fields marked `#[env(file)]` also describe their `_FILE` variable, either of which satisfies a requirement.
+///
+/// If several variables share a name, such as the fields of different enum variants, only the first is described.
+///
+/// ```rust
+/// use derive_environment::{schema, EnvSchema, FromEnv};
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     /// Port to listen on.
+///     #[env(required)]
+///     port: u16,
+/// }
+///
+/// assert_eq!(schema::json_schema(&Config::env_schema("APP")), r#"{
+///   "$schema": "https://json-schema.org/draft/2020-12/schema",
+///   "type": "object",
+///   "properties": {
+///     "APP_PORT": {
+///       "description": "Port to listen on.",
+///       "type": [
+///         "integer",
+///         "string"
+///       ],
+///       "minimum": 0,
+///       "maximum": 65535,
+///       "pattern": "^\\+?0*(?:[0-9]|[1-9][0-9]{1,3}|[1-5][0-9]{4}|6(?:[0-4][0-9]{3}|5(?:[0-4][0-9]{2}|5(?:[0-2][0-9]|3[0-5]))))$"
+///     }
+///   },
+///   "required": [
+///     "APP_PORT"
+///   ]
+/// }
+/// "#);
+/// ```
+pub fn json_schema(vars: &[EnvVar]) -> String {
+	let mut properties = Vec::new();
+	let mut pattern_properties = Vec::new();
+	let mut required = Vec::new();
+	let mut required_files = Vec::new();
+
+	for var in vars {
+		let (key, properties) = if var.is_pattern() {
+			let key = format!("^{}$", escape(&var.name))
+				.replace("<n>", "[0-9]+")
+				.replace("<key>", ".+");
+			(key, &mut pattern_properties)
+		} else {
+			(var.name.clone(), &mut properties)
+		};
+		if properties.iter().any(|(existing, _)| *existing == key) {
+			continue;
+		}
+
+		let mut schema = Vec::new();
+		if let Some(doc) = &var.doc {
+			schema.push((String::from("description"), Json::string(doc)));
+		}
+		if let Some(default) = &var.default {
+			schema.push((String::from("default"), Json::string(default)));
+		}
+		schema.extend(kind_schema(&var.kind));
+
+		let file_key = match key.strip_suffix('$') {
+			Some(pattern) => format!("{pattern}_FILE$"),
+			None => format!("{key}_FILE"),
+		};
+		properties.push((key, Json::Object(schema)));
+
+		if var.file {
+			let description = format!("Path of a file containing {}.", var.name);
+			let schema = vec![
+				(String::from("description"), Json::String(description)),
+				(String::from("type"), Json::string("string")),
+			];
+			properties.push((file_key.clone(), Json::Object(schema)));
+		}
+
+		if var.required && !var.is_pattern() {
+			if var.file {
+				// Either the variable or its file satisfies the requirement.
+				let options = [&var.name, &file_key].map(|name| {
+					let required = Json::Array(vec![Json::string(name)]);
+					Json::Object(vec![(String::from("required"), required)])
+				});
+				let any_of = (String::from("anyOf"), Json::Array(options.into()));
+				required_files.push(Json::Object(vec![any_of]));
+			} else {
+				required.push(Json::string(&var.name));
+			}
+		}
+	}
+
+	let mut root = vec![
+		(
+			String::from("$schema"),
+			Json::string("https://json-schema.org/draft/2020-12/schema"),
+		),
+		(String::from("type"), Json::string("object")),
+	];
+	if !properties.is_empty() {
+		root.push((String::from("properties"), Json::Object(properties)));
+	}
+	if !pattern_properties.is_empty() {
+		root.push((
+			String::from("patternProperties"),
+			Json::Object(pattern_properties),
+		));
+	}
+	if !required.is_empty() {
+		root.push((String::from("required"), Json::Array(required)));
+	}
+	if !required_files.is_empty() {
+		root.push((String::from("allOf"), Json::Array(required_files)));
+	}
+
+	let mut out = String::new();
+	Json::Object(root).write(&mut out, 0);
+	out.push('\n');
+	out
+}
+
+/// Describes the values of `kind` using JSON Schema keywords.
+fn kind_schema(kind: &ValueKind) -> Vec<(String, Json)> {
+	let types = |types: &[&str]| {
+		let types = types.iter().copied().map(Json::string).collect();
+		(String::from("type"), Json::Array(types))
+	};
+	let pattern = |pattern: String| (String::from("pattern"), Json::String(pattern));
+	let string = (String::from("type"), Json::string("string"));
+
+	match kind {
+		ValueKind::Any => vec![string],
+		ValueKind::Bool | ValueKind::Flag => {
+			let names: Vec<String> = boolean::TRUTHY
+				.iter()
+				.chain(boolean::FALSY)
+				.map(|name| insensitive(name))
+				.collect();
+			let optional = if *kind == ValueKind::Flag { "?" } else { "" };
+			vec![
+				types(&["boolean", "string"]),
+				pattern(format!(r"^\s*(?:{}){optional}\s*$", names.join("|"))),
+			]
+		}
+		ValueKind::Integer { min, max, nonzero } => {
+			let least = u128::from(*nonzero);
+			let mut signs = Vec::new();
+			if let Ok(min) = u128::try_from(*min) {
+				signs.push(format!(r"\+?0*{}", digit_range(min.max(least), *max)));
+			} else {
+				signs.push(format!(r"\+?0*{}", digit_range(least, *max)));
+				signs.push(format!("-0*{}", digit_range(least, min.unsigned_abs())));
+			}
+			let mut schema = vec![
+				types(&["integer", "string"]),
+				(String::from("minimum"), Json::Number(min.to_string())),
+				(String::from("maximum"), Json::Number(max.to_string())),
+			];
+			if *nonzero && *min < 0 {
+				let zero = vec![(String::from("const"), Json::Number(String::from("0")))];
+				schema.push((String::from("not"), Json::Object(zero)));
+			}
+			schema.push(pattern(format!("^{}$", alternatives(&signs))));
+			schema
+		}
+		ValueKind::Float => vec![
+			types(&["number", "string"]),
+			pattern(format!(
+				r"^[+-]?(?:{}(?:{})?|{}|(?:[0-9]+\.?[0-9]*|\.[0-9]+)(?:[eE][+-]?[0-9]+)?)$",
+				insensitive("inf"),
+				insensitive("inity"),
+				insensitive("nan"),
+			)),
+		],
+		ValueKind::Char => vec![
+			string,
+			(String::from("minLength"), Json::Number(String::from("1"))),
+			(String::from("maxLength"), Json::Number(String::from("1"))),
+		],
+		ValueKind::OneOf(names) => {
+			let names: Vec<String> = names.iter().map(|name| insensitive(name)).collect();
+			vec![string, pattern(format!("^(?:{})$", names.join("|")))]
+		}
+		ValueKind::Pattern(regex) => vec![string, pattern(regex.clone())],
+	}
+}
+
+/// Builds a regular expression matching the decimal numbers from `min` to `max`, without leading zeros.
+fn digit_range(min: u128, max: u128) -> String {
+	if min > max {
+		// An empty lookahead never matches.
+		return String::from("(?!)");
+	}
+	let (min, max) = (min.to_string(), max.to_string());
+	let mut ranges = Vec::new();
+
+	if min.len() == max.len() {
+		ranges.extend(same_length_range(min.as_bytes(), max.as_bytes()));
+	} else {
+		// Numbers with more digits than `min` and fewer than `max` may use any digits.
+		let nines = "9".repeat(min.len());
+		let ten = format!("1{}", "0".repeat(max.len() - 1));
+		ranges.extend(same_length_range(min.as_bytes(), nines.as_bytes()));
+		if max.len() - min.len() > 1 {
+			ranges.push(format!("[1-9]{}", any_digits(min.len(), max.len() - 2)));
+		}
+		ranges.extend(same_length_range(ten.as_bytes(), max.as_bytes()));
+	}
+
+	alternatives(&ranges)
+}
+
+/// Builds regular expressions which together match the numbers from `min` to `max`, which have the same number of digits.
+fn same_length_range(min: &[u8], max: &[u8]) -> Vec<String> {
+	let class = |min: u8, max: u8| match min == max {
+		true => char::from(min).to_string(),
+		false => format!("[{}-{}]", char::from(min), char::from(max)),
+	};
+	let (first, last) = (min[0], max[0]);
+	let rest = min.len() - 1;
+	let then = |first: u8, min: &[u8], max: &[u8]| {
+		format!(
+			"{}{}",
+			char::from(first),
+			alternatives(&same_length_range(min, max))
+		)
+	};
+	if rest == 0 {
+		return vec![class(first, last)];
+	}
+	if first == last {
+		return vec![then(first, &min[1..], &max[1..])];
+	}
+
+	// Split off the first and last leading digits when they don't cover every number below them.
+	let mut ranges = Vec::new();
+	let mut middle = (first, last);
+	if min[1..].iter().any(|&digit| digit != b'0') {
+		let nines = vec![b'9'; rest];
+		ranges.push(then(first, &min[1..], &nines));
+		middle.0 += 1;
+	}
+	let partial_last = max[1..].iter().any(|&digit| digit != b'9');
+	if partial_last {
+		middle.1 -= 1;
+	}
+	if middle.0 <= middle.1 {
+		ranges.push(format!(
+			"{}{}",
+			class(middle.0, middle.1),
+			any_digits(rest, rest)
+		));
+	}
+	if partial_last {
+		let zeros = vec![b'0'; rest];
+		ranges.push(then(last, &zeros, &max[1..]));
+	}
+	ranges
+}
+
+/// Joins regular expressions into one matching any of them.
+fn alternatives(patterns: &[String]) -> String {
+	match patterns {
+		[pattern] => pattern.clone(),
+		patterns => format!("(?:{})", patterns.join("|")),
+	}
+}
+
+/// Builds a regular expression matching between `min` and `max` digits.
+fn any_digits(min: usize, max: usize) -> String {
+	match (min, max) {
+		(1, 1) => String::from("[0-9]"),
+		(min, max) if min == max => format!("[0-9]{{{min}}}"),
+		(min, max) => format!("[0-9]{{{min},{max}}}"),
+	}
+}
+
+/// Escapes the characters of `s` which have a meaning in regular expressions.
+fn escape(s: &str) -> String {
+	let mut escaped = String::with_capacity(s.len());
+	for c in s.chars() {
+		if r"\^$.|?*+()[]{}/".contains(c) {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
+
+/// Builds a regular expression matching `s`, ignoring the case of ASCII letters.
+pub(crate) fn insensitive(s: &str) -> String {
+	let mut pattern = String::new();
+	for c in escape(s).chars() {
+		if c.is_ascii_alphabetic() {
+			pattern.extend(['[', c.to_ascii_lowercase(), c.to_ascii_uppercase(), ']']);
+		} else {
+			pattern.push(c);
+		}
+	}
+	pattern
+}
+
+/// A JSON value, as written by [`json_schema`].
+enum Json {
+	String(String),
+	Number(String),
+	Array(Vec<Json>),
+	Object(Vec<(String, Json)>),
+}
+
+impl Json {
+	fn string(s: &str) -> Self {
+		Self::String(s.to_string())
+	}
+
+	/// Writes this value to `out`, indenting nested lines by `depth` levels.
+	fn write(&self, out: &mut String, depth: usize) {
+		let indent = |out: &mut String, depth: usize| out.push_str(&"  ".repeat(depth));
+
+		match self {
+			Json::String(s) => write_string(out, s),
+			Json::Number(n) => out.push_str(n),
+			Json::Array(items) => {
+				out.push('[');
+				for (i, item) in items.iter().enumerate() {
+					out.push_str(if i == 0 { "\n" } else { ",\n" });
+					indent(out, depth + 1);
+					item.write(out, depth + 1);
+				}
+				if !items.is_empty() {
+					out.push('\n');
+					indent(out, depth);
+				}
+				out.push(']');
+			}
+			Json::Object(entries) => {
+				out.push('{');
+				for (i, (key, value)) in entries.iter().enumerate() {
+					out.push_str(if i == 0 { "\n" } else { ",\n" });
+					indent(out, depth + 1);
+					write_string(out, key);
+					out.push_str(": ");
+					value.write(out, depth + 1);
+				}
+				if !entries.is_empty() {
+					out.push('\n');
+					indent(out, depth);
+				}
+				out.push('}');
+			}
+		}
+	}
+}
+
+/// Writes `s` to `out` as a JSON string.
+fn write_string(out: &mut String, s: &str) {
+	out.push('"');
+	for c in s.chars() {
+		match c {
+			'"' => out.push_str("\\\""),
+			'\\' => out.push_str("\\\\"),
+			'\n' => out.push_str("\\n"),
+			'\r' => out.push_str("\\r"),
+			'\t' => out.push_str("\\t"),
+			c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+			c => out.push(c),
+		}
+	}
+	out.push('"');
+}
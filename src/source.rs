@@ -0,0 +1,253 @@
+//! Sources that environment variables may be read from.
+
+use std::{
+	borrow::Borrow,
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
+	env::{self, VarError},
+	ffi::{OsStr, OsString},
+	hash::{BuildHasher, Hash},
+};
+
+/// A place that environment variables can be read from.
+///
+/// [`FromEnv::with_env`](crate::FromEnv::with_env) reads from [`ProcessEnv`],
+/// but any source may be passed to [`FromEnv::with_env_from`](crate::FromEnv::with_env_from).
+/// This allows a structure to be populated without touching the global process environment.
+///
+/// ```rust
+/// use derive_environment::{EnvSource, FromEnv};
+/// use std::collections::HashMap;
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     port: u16,
+/// }
+///
+/// let vars = HashMap::from([(String::from("APP_PORT"), String::from("8080"))]);
+///
+/// let mut config = Config::default();
+/// config.with_env_from(&vars, "APP").unwrap();
+/// assert_eq!(config.port, 8080);
+/// ```
+pub trait EnvSource {
+	/// Fetches the value of `key`, or `None` if it is not set.
+	fn var_os(&self, key: &str) -> Option<OsString>;
+
+	/// Fetches the value of `key` as a unicode string.
+	///
+	/// This mirrors [`std::env::var`].
+	fn var(&self, key: &str) -> Result<String, VarError> {
+		match self.var_os(key) {
+			Some(s) => s.into_string().map_err(VarError::NotUnicode),
+			None => Err(VarError::NotPresent),
+		}
+	}
+
+	/// Lists the name of every variable in this source.
+	///
+	/// This is used by types which must scan for variables, such as maps.
+	/// Sources which cannot be listed (like [`from_fn`]) return nothing, so scanning will never find anything in them.
+	fn keys(&self) -> Vec<String> {
+		Vec::new()
+	}
+
+	/// Layers `fallback` beneath this source.
+	///
+	/// Variables are looked up in `self` first, and only read from `fallback` if they are absent.
+	fn or<S: EnvSource>(self, fallback: S) -> Layered<Self, S>
+	where
+		Self: Sized,
+	{
+		Layered {
+			primary: self,
+			fallback,
+		}
+	}
+}
+
+/// Reads variables from the environment of the current process.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcessEnv;
+
+impl EnvSource for ProcessEnv {
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		env::var_os(key)
+	}
+
+	fn keys(&self) -> Vec<String> {
+		// Names which are not unicode could never be requested.
+		env::vars_os()
+			.filter_map(|(key, _)| key.into_string().ok())
+			.collect()
+	}
+}
+
+/// Reads variables by calling a closure.
+///
+/// Created by [`from_fn`].
+#[derive(Clone, Copy, Debug)]
+pub struct FnSource<F>(F);
+
+/// Creates an [`EnvSource`] which looks up variables using `f`.
+///
+/// ```rust
+/// use derive_environment::{source, FromEnv};
+///
+/// let source = source::from_fn(|key| (key == "PORT").then(|| String::from("8080")));
+///
+/// let mut port = 0u16;
+/// port.with_env_from(&source, "PORT").unwrap();
+/// assert_eq!(port, 8080);
+/// ```
+pub fn from_fn<F, V>(f: F) -> FnSource<F>
+where
+	F: Fn(&str) -> Option<V>,
+	V: Into<OsString>,
+{
+	FnSource(f)
+}
+
+impl<F, V> EnvSource for FnSource<F>
+where
+	F: Fn(&str) -> Option<V>,
+	V: Into<OsString>,
+{
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		(self.0)(key).map(Into::into)
+	}
+}
+
+/// Two sources stacked on top of each other.
+///
+/// Created by [`EnvSource::or`].
+#[derive(Clone, Copy, Debug)]
+pub struct Layered<A, B> {
+	primary: A,
+	fallback: B,
+}
+
+impl<A: EnvSource, B: EnvSource> EnvSource for Layered<A, B> {
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		self.primary
+			.var_os(key)
+			.or_else(|| self.fallback.var_os(key))
+	}
+
+	fn keys(&self) -> Vec<String> {
+		let mut keys = self.primary.keys();
+		for key in self.fallback.keys() {
+			if !keys.contains(&key) {
+				keys.push(key);
+			}
+		}
+		keys
+	}
+}
+
+impl<S: EnvSource + ?Sized> EnvSource for &S {
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		(**self).var_os(key)
+	}
+
+	fn keys(&self) -> Vec<String> {
+		(**self).keys()
+	}
+}
+
+impl<K, V, H> EnvSource for HashMap<K, V, H>
+where
+	K: Borrow<str> + Hash + Eq,
+	V: AsRef<OsStr>,
+	H: BuildHasher,
+{
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		self.get(key).map(|v| v.as_ref().to_owned())
+	}
+
+	fn keys(&self) -> Vec<String> {
+		HashMap::keys(self)
+			.map(|k| k.borrow().to_string())
+			.collect()
+	}
+}
+
+impl<K, V> EnvSource for BTreeMap<K, V>
+where
+	K: Borrow<str> + Ord,
+	V: AsRef<OsStr>,
+{
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		self.get(key).map(|v| v.as_ref().to_owned())
+	}
+
+	fn keys(&self) -> Vec<String> {
+		BTreeMap::keys(self)
+			.map(|k| k.borrow().to_string())
+			.collect()
+	}
+}
+
+impl<K, V> EnvSource for [(K, V)]
+where
+	K: AsRef<str>,
+	V: AsRef<OsStr>,
+{
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		self.iter()
+			.find(|(k, _)| k.as_ref() == key)
+			.map(|(_, v)| v.as_ref().to_owned())
+	}
+
+	fn keys(&self) -> Vec<String> {
+		self.iter().map(|(k, _)| k.as_ref().to_string()).collect()
+	}
+}
+
+impl<K, V, const N: usize> EnvSource for [(K, V); N]
+where
+	K: AsRef<str>,
+	V: AsRef<OsStr>,
+{
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		self.as_slice().var_os(key)
+	}
+
+	fn keys(&self) -> Vec<String> {
+		self.as_slice().keys()
+	}
+}
+
+/// An [`EnvSource`] which records every variable that was read from it.
+pub(crate) struct Recorder<'a> {
+	source: &'a dyn EnvSource,
+	read: RefCell<Vec<String>>,
+}
+
+impl<'a> Recorder<'a> {
+	pub(crate) fn new(source: &'a dyn EnvSource) -> Self {
+		Self {
+			source,
+			read: RefCell::new(Vec::new()),
+		}
+	}
+
+	/// Returns the name of every variable which was present when read.
+	pub(crate) fn into_read(self) -> Vec<String> {
+		self.read.into_inner()
+	}
+}
+
+impl EnvSource for Recorder<'_> {
+	fn var_os(&self, key: &str) -> Option<OsString> {
+		let value = self.source.var_os(key);
+		if value.is_some() {
+			self.read.borrow_mut().push(key.to_string());
+		}
+		value
+	}
+
+	fn keys(&self) -> Vec<String> {
+		self.source.keys()
+	}
+}
@@ -0,0 +1,287 @@
+//! Reading quantities written with units, such as `1m30s` or `64KiB`.
+
+use crate::{
+	impl_using_from_str,
+	schema::{self, ValueKind},
+	EnvSchema, EnvSource, EnvVar, FromEnv, Result,
+};
+use std::{fmt, str::FromStr, time::Duration};
+
+/// Errors generated when parsing a [`Duration`] or [`ByteSize`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum UnitError {
+	/// The value was empty, or only whitespace.
+	#[error("value is empty")]
+	Empty,
+	/// A number was expected, but something else was found.
+	#[error("expected a number at {0:?}")]
+	ExpectedNumber(String),
+	/// A number was not followed by a unit.
+	#[error("missing unit after {0}")]
+	MissingUnit(String),
+	/// A number was followed by an unrecognized unit.
+	#[error("unknown unit {0:?}")]
+	UnknownUnit(String),
+	/// The value does not fit in the type being parsed.
+	#[error("value is too large")]
+	Overflow,
+	/// The value was a fraction of the smallest unit.
+	#[error("{0} is not a whole number of bytes")]
+	Fractional(String),
+}
+
+/// Nanoseconds in each duration unit.
+const DURATION_UNITS: &[(&str, u128)] = &[
+	("ns", 1),
+	("us", 1_000),
+	("µs", 1_000),
+	("ms", 1_000_000),
+	("s", 1_000_000_000),
+	("m", 60_000_000_000),
+	("min", 60_000_000_000),
+	("h", 3_600_000_000_000),
+	("d", 86_400_000_000_000),
+];
+
+/// Bytes in each byte size unit.
+const BYTE_UNITS: &[(&str, u128)] = &[
+	("b", 1),
+	("kb", 1_000),
+	("mb", 1_000_000),
+	("gb", 1_000_000_000),
+	("tb", 1_000_000_000_000),
+	("pb", 1_000_000_000_000_000),
+	("eb", 1_000_000_000_000_000_000),
+	("kib", 1 << 10),
+	("mib", 1 << 20),
+	("gib", 1 << 30),
+	("tib", 1 << 40),
+	("pib", 1 << 50),
+	("eib", 1 << 60),
+];
+
+/// Fractional digits beyond this are ignored, keeping the arithmetic within a `u128`.
+const MAX_FRACTION_DIGITS: usize = 18;
+
+/// Parses a duration made of one or more numbers, each followed by a unit.
+///
+/// The units are `ns`, `us` (or `µs`), `ms`, `s`, `m` (or `min`), `h` and `d`.
+/// Numbers may have a fractional part, and whitespace may separate each part.
+/// A bare `0` is accepted without a unit.
+///
+/// ```rust
+/// use derive_environment::units::{parse_duration, UnitError};
+/// use std::time::Duration;
+///
+/// assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+/// assert_eq!(parse_duration("1m30s"), Ok(Duration::from_secs(90)));
+/// assert_eq!(parse_duration("1h 30m"), Ok(Duration::from_secs(5400)));
+/// assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+/// assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+/// assert_eq!(parse_duration("0"), Ok(Duration::ZERO));
+///
+/// assert_eq!(parse_duration(""), Err(UnitError::Empty));
+/// assert_eq!(parse_duration("30"), Err(UnitError::MissingUnit(String::from("30"))));
+/// assert_eq!(parse_duration("30 sec"), Err(UnitError::UnknownUnit(String::from("sec"))));
+/// assert_eq!(parse_duration("1m s"), Err(UnitError::ExpectedNumber(String::from("s"))));
+/// assert_eq!(parse_duration("-1s"), Err(UnitError::ExpectedNumber(String::from("-1s"))));
+/// assert_eq!(parse_duration("18446744073709551615s"), Ok(Duration::from_secs(u64::MAX)));
+/// assert_eq!(parse_duration("18446744073709551616s"), Err(UnitError::Overflow));
+/// assert_eq!(parse_duration("999999999999999999999999999999999999999d"), Err(UnitError::Overflow));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error describing the first part of `s` which could not be parsed.
+pub fn parse_duration(s: &str) -> std::result::Result<Duration, UnitError> {
+	let s = s.trim();
+	if s == "0" {
+		return Ok(Duration::ZERO);
+	}
+
+	let nanos = parse_quantity(s, DURATION_UNITS, false)?;
+	let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| UnitError::Overflow)?;
+	// The remainder is always less than a second.
+	Ok(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// Reads a duration using [`parse_duration`].
+///
+/// ```rust
+/// use derive_environment::FromEnv;
+/// use std::time::Duration;
+///
+/// let mut timeout = Duration::ZERO;
+/// timeout.with_env_from(&[("TIMEOUT", "1m30s")], "TIMEOUT").unwrap();
+/// assert_eq!(timeout, Duration::from_secs(90));
+/// ```
+impl FromEnv for Duration {
+	fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+		crate::__private::parse_with(self, source, var, parse_duration)
+	}
+}
+
+impl EnvSchema for Duration {
+	fn env_schema(prefix: &str) -> Vec<EnvVar> {
+		let units = unit_pattern(DURATION_UNITS);
+		let pattern = format!(r"^\s*(?:0\s*|(?:[0-9]+(?:\.[0-9]*)?\s*{units}\s*)+)$");
+		vec![EnvVar::leaf_with::<Self>(
+			prefix,
+			ValueKind::Pattern(pattern),
+		)]
+	}
+}
+
+/// A number of bytes, parsed from a number followed by an optional unit.
+///
+/// Decimal units (`KB`, `MB`, `GB`, `TB`, `PB`, `EB`) are powers of 1000,
+/// while binary units (`KiB`, `MiB`, `GiB`, `TiB`, `PiB`, `EiB`) are powers of 1024.
+/// Units are case-insensitive, and a number without a unit is a number of bytes.
+/// Numbers may have a fractional part, as long as the result is a whole number of bytes.
+///
+/// ```rust
+/// use derive_environment::units::{ByteSize, UnitError};
+///
+/// assert_eq!("64KiB".parse(), Ok(ByteSize(65_536)));
+/// assert_eq!("1.5GB".parse(), Ok(ByteSize(1_500_000_000)));
+/// assert_eq!("1.5 kib".parse(), Ok(ByteSize(1536)));
+/// assert_eq!("512".parse(), Ok(ByteSize(512)));
+/// assert_eq!("16EiB".parse::<ByteSize>(), Err(UnitError::Overflow));
+/// assert_eq!("1.5B".parse::<ByteSize>(), Err(UnitError::Fractional(String::from("1.5B"))));
+/// assert_eq!("10 bits".parse::<ByteSize>(), Err(UnitError::UnknownUnit(String::from("bits"))));
+/// assert_eq!("1KiB 2".parse::<ByteSize>(), Err(UnitError::UnknownUnit(String::from("KiB 2"))));
+///
+/// // Sizes are displayed using the largest binary unit which divides them exactly.
+/// assert_eq!(ByteSize(65_536).to_string(), "64KiB");
+/// assert_eq!(ByteSize(1000).to_string(), "1000B");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl From<u64> for ByteSize {
+	fn from(bytes: u64) -> Self {
+		Self(bytes)
+	}
+}
+
+impl From<ByteSize> for u64 {
+	fn from(size: ByteSize) -> Self {
+		size.0
+	}
+}
+
+impl FromStr for ByteSize {
+	type Err = UnitError;
+
+	fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+		let s = s.trim();
+		let bytes = parse_quantity(s, BYTE_UNITS, true)?;
+		u64::try_from(bytes)
+			.map(Self)
+			.map_err(|_| UnitError::Overflow)
+	}
+}
+
+impl fmt::Display for ByteSize {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		const UNITS: [&str; 6] = ["EiB", "PiB", "TiB", "GiB", "MiB", "KiB"];
+
+		if self.0 != 0 {
+			for (i, unit) in UNITS.iter().enumerate() {
+				let size = 1u64 << (10 * (UNITS.len() - i));
+				if self.0.is_multiple_of(size) {
+					return write!(f, "{}{unit}", self.0 / size);
+				}
+			}
+		}
+		write!(f, "{}B", self.0)
+	}
+}
+
+impl_using_from_str!(ByteSize => {
+	let units = unit_pattern(BYTE_UNITS);
+	ValueKind::Pattern(format!(r"^\s*[0-9]+(?:\.[0-9]*)?\s*{units}?\s*$"))
+});
+
+/// Builds a regular expression matching any of `units`, ignoring case.
+fn unit_pattern(units: &[(&str, u128)]) -> String {
+	let mut names: Vec<&str> = units.iter().map(|(name, _)| *name).collect();
+	// Longer units are tried first, so that `ms` isn't read as `m` followed by `s`.
+	names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+	let names: Vec<String> = names.into_iter().map(schema::insensitive).collect();
+	format!("(?:{})", names.join("|"))
+}
+
+/// Parses a sum of numbers and units, returning the total in the smallest unit.
+///
+/// If `single` is set, only one number is accepted and its unit may be omitted.
+fn parse_quantity(
+	s: &str,
+	units: &[(&str, u128)],
+	single: bool,
+) -> std::result::Result<u128, UnitError> {
+	if s.is_empty() {
+		return Err(UnitError::Empty);
+	}
+
+	let mut total: u128 = 0;
+	let mut rest = s;
+
+	while !rest.is_empty() {
+		let number_len = rest
+			.find(|c: char| !c.is_ascii_digit() && c != '.')
+			.unwrap_or(rest.len());
+		let number = &rest[..number_len];
+		let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+		if whole.is_empty() || fraction.contains('.') {
+			return Err(UnitError::ExpectedNumber(rest.to_string()));
+		}
+		rest = rest[number_len..].trim_start();
+
+		// A single number's unit is everything after it.
+		let unit_len = if single {
+			rest.len()
+		} else {
+			rest.find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+				.unwrap_or(rest.len())
+		};
+		let unit = &rest[..unit_len];
+		rest = rest[unit_len..].trim_start();
+
+		let scale = if unit.is_empty() {
+			if !single {
+				return Err(UnitError::MissingUnit(number.to_string()));
+			}
+			1
+		} else {
+			units
+				.iter()
+				.find(|(name, _)| name.eq_ignore_ascii_case(unit))
+				.map(|(_, scale)| *scale)
+				.ok_or_else(|| UnitError::UnknownUnit(unit.to_string()))?
+		};
+
+		let whole: u128 = whole.parse().map_err(|_| UnitError::Overflow)?;
+		let fraction = &fraction[..fraction.len().min(MAX_FRACTION_DIGITS)];
+		let numerator: u128 = if fraction.is_empty() {
+			0
+		} else {
+			// Only digits remain, and there are too few of them to overflow.
+			fraction.parse().unwrap_or(0)
+		};
+		let denominator = 10u128.pow(fraction.len() as u32);
+
+		let fractional = numerator * scale;
+		if single && !fractional.is_multiple_of(denominator) {
+			return Err(UnitError::Fractional(s.to_string()));
+		}
+
+		total = whole
+			.checked_mul(scale)
+			.and_then(|whole| whole.checked_add(fractional / denominator))
+			.and_then(|value| total.checked_add(value))
+			.ok_or(UnitError::Overflow)?;
+	}
+
+	Ok(total)
+}
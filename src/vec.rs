@@ -0,0 +1,544 @@
+//! Reading vectors, either from indexed variables or from a single delimited variable,
+//! and combining them with the vector's existing elements.
+//!
+//! Indices are joined to the prefix with `_`, or with the separator of the derived type being loaded
+//! (see `#[env(separator = "...")]`).
+
+use crate::{
+	__private::separator, schema, EnvSchema, EnvSource, EnvVar, FailFast, FromEnv, FromEnvError,
+	FromEnvErrors, PathSegment, Result, Sink,
+};
+use std::{
+	collections::BTreeSet,
+	env::VarError,
+	ops::{Deref, DerefMut},
+};
+
+/// Controls how a [`Vec`] is read.
+///
+/// When deriving, these are set using field attributes such as `#[env(delimiter = ",")]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VecOptions {
+	/// If set, the vector is first read from a single variable (`PREFIX`) whose elements are separated by this character.
+	/// Indexed variables are only read if this variable is absent.
+	pub delimiter: Option<char>,
+	/// How indexed variables are found.
+	pub indices: Indices,
+	/// How loaded elements are combined with the existing vector.
+	pub mode: VecMode,
+}
+
+/// Determines how loaded elements are combined with a [`Vec`]'s existing elements.
+///
+/// When deriving, this is set using `#[env(vec_mode = "...")]`.
+///
+/// ```rust
+/// use derive_environment::FromEnv;
+///
+/// #[derive(Clone, Debug, Default, PartialEq, FromEnv)]
+/// struct Server {
+///     host: String,
+///     port: u16,
+/// }
+///
+/// #[derive(Default, FromEnv)]
+/// struct Config {
+///     #[env(vec_mode = "merge")]
+///     servers: Vec<Server>,
+///     #[env(vec_mode = "append")]
+///     tags: Vec<String>,
+/// }
+///
+/// let a = Server { host: String::from("a.com"), port: 80 };
+/// let b = Server { host: String::from("b.com"), port: 80 };
+/// let mut config = Config {
+///     servers: vec![a.clone(), b.clone()],
+///     tags: vec![String::from("prod")],
+/// };
+///
+/// let source = [("APP_SERVERS_1_PORT", "8080"), ("APP_TAGS_0", "eu")];
+/// config.with_env_from(&source, "APP").unwrap();
+///
+/// assert_eq!(config.servers, [a, Server { port: 8080, ..b }]);
+/// assert_eq!(config.tags, ["prod", "eu"]);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VecMode {
+	/// Replaces the whole vector with the loaded elements, if any were found.
+	#[default]
+	Replace,
+	/// Loads each index into the existing element at that index, pushing any which are beyond the end of the vector.
+	Merge,
+	/// Pushes the loaded elements after the existing ones.
+	Append,
+}
+
+/// Determines how the indexed variables of a [`Vec`] are found.
+///
+/// When deriving, this is set using `#[env(indices = "...")]`.
+///
+/// ```rust
+/// use derive_environment::{vec::{self, Indices, VecOptions}, FromEnvError};
+///
+/// let source = [("LIST_0", "a"), ("LIST_2", "c"), ("LIST_10", "k")];
+/// let mut list = Vec::<String>::new();
+///
+/// let mut options = VecOptions::default();
+/// vec::with_env_options(&mut list, &source, "LIST", &options).unwrap();
+/// assert_eq!(list, ["a"]);
+///
+/// options.indices = Indices::Compact;
+/// vec::with_env_options(&mut list, &source, "LIST", &options).unwrap();
+/// assert_eq!(list, ["a", "c", "k"]);
+///
+/// options.indices = Indices::Strict;
+/// let error = vec::with_env_options(&mut list, &source, "LIST", &options).unwrap_err();
+/// assert!(matches!(error, FromEnvError::IndexGap { index: 1, .. }));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Indices {
+	/// Reads `PREFIX_0`, `PREFIX_1`, and so on, stopping at the first index which is absent.
+	///
+	/// This does not need to list the source's variables,
+	/// but silently ignores any elements after a gap.
+	#[default]
+	Contiguous,
+	/// Scans the source for every `PREFIX_<n>`, and reads them in numerical order, skipping any gaps.
+	Compact,
+	/// Like [`Indices::Compact`], but fails with [`FromEnvError::IndexGap`] if any index is absent.
+	Strict,
+}
+
+/// Reads `v` from `source` according to `options`.
+/// Returns `Ok(true)` if any variable was found and used, and `Ok(false)` if they were all absent.
+///
+/// # Errors
+///
+/// Throws an error if a variable could not be read or parsed;
+pub fn with_env_options<T: FromEnv + Default>(
+	v: &mut Vec<T>,
+	source: &dyn EnvSource,
+	prefix: &str,
+	options: &VecOptions,
+) -> Result<bool> {
+	load(v, source, prefix, options, &mut FailFast)
+}
+
+/// Like [`with_env_options`], but pushes errors to `errors` instead of returning early.
+pub fn collect_env_options<T: FromEnv + Default>(
+	v: &mut Vec<T>,
+	source: &dyn EnvSource,
+	prefix: &str,
+	options: &VecOptions,
+	errors: &mut FromEnvErrors,
+) -> bool {
+	// Collecting never returns an error.
+	load(v, source, prefix, options, errors).unwrap_or(false)
+}
+
+impl<T: FromEnv + Default> FromEnv for Vec<T> {
+	fn with_env_from(&mut self, source: &dyn EnvSource, prefix: &str) -> Result<bool> {
+		with_env_options(self, source, prefix, &VecOptions::default())
+	}
+
+	fn collect_env_from(
+		&mut self,
+		source: &dyn EnvSource,
+		prefix: &str,
+		errors: &mut FromEnvErrors,
+	) -> bool {
+		collect_env_options(self, source, prefix, &VecOptions::default(), errors)
+	}
+}
+
+/// A vector which may be read from a single variable, with elements separated by `SEP`.
+///
+/// Elements may contain `SEP` if it is escaped with `\` or wrapped in double quotes.
+/// Whitespace surrounding each element is ignored unless it is escaped or quoted.
+/// If the variable is absent, indexed variables (`PREFIX_0`, `PREFIX_1`, ...) are read instead, like a [`Vec`].
+///
+/// ```rust
+/// use derive_environment::{vec::Delimited, FromEnv};
+///
+/// let mut hosts = Delimited::<String>::default();
+///
+/// hosts.with_env_from(&[("HOSTS", r#"a.com, "b,c.com", d\,e.com"#)], "HOSTS").unwrap();
+/// assert_eq!(*hosts, ["a.com", "b,c.com", "d,e.com"]);
+///
+/// hosts.with_env_from(&[("HOSTS_0", "x.com"), ("HOSTS_1", "y.com")], "HOSTS").unwrap();
+/// assert_eq!(*hosts, ["x.com", "y.com"]);
+///
+/// let mut ports = Delimited::<u16, ':'>::default();
+/// ports.with_env_from(&[("PORTS", "80:443")], "PORTS").unwrap();
+/// assert_eq!(*ports, [80, 443]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Delimited<T, const SEP: char = ','>(pub Vec<T>);
+
+impl<T: EnvSchema> EnvSchema for Vec<T> {
+	fn env_schema(prefix: &str) -> Vec<EnvVar> {
+		schema::within(
+			T::env_schema(&format!("{prefix}{}<n>", separator())),
+			PathSegment::AnyIndex,
+		)
+	}
+}
+
+impl<T, const SEP: char> Default for Delimited<T, SEP> {
+	fn default() -> Self {
+		Self(Vec::new())
+	}
+}
+
+impl<T, const SEP: char> From<Vec<T>> for Delimited<T, SEP> {
+	fn from(v: Vec<T>) -> Self {
+		Self(v)
+	}
+}
+
+impl<T, const SEP: char> Deref for Delimited<T, SEP> {
+	type Target = Vec<T>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.0
+	}
+}
+
+impl<T, const SEP: char> DerefMut for Delimited<T, SEP> {
+	fn deref_mut(&mut self) -> &mut Self::Target {
+		&mut self.0
+	}
+}
+
+impl<T: FromEnv + Default, const SEP: char> FromEnv for Delimited<T, SEP> {
+	fn with_env_from(&mut self, source: &dyn EnvSource, prefix: &str) -> Result<bool> {
+		with_env_options(&mut self.0, source, prefix, &Self::OPTIONS)
+	}
+
+	fn collect_env_from(
+		&mut self,
+		source: &dyn EnvSource,
+		prefix: &str,
+		errors: &mut FromEnvErrors,
+	) -> bool {
+		collect_env_options(&mut self.0, source, prefix, &Self::OPTIONS, errors)
+	}
+}
+
+/// Lists the delimited variable, followed by the indexed variables it falls back to.
+impl<T: EnvSchema, const SEP: char> EnvSchema for Delimited<T, SEP> {
+	fn env_schema(prefix: &str) -> Vec<EnvVar> {
+		let mut vars = vec![EnvVar::leaf::<Self>(prefix)];
+		vars.extend(Vec::<T>::env_schema(prefix));
+		vars
+	}
+}
+
+impl<T, const SEP: char> Delimited<T, SEP> {
+	const OPTIONS: VecOptions = VecOptions {
+		delimiter: Some(SEP),
+		indices: Indices::Contiguous,
+		mode: VecMode::Replace,
+	};
+}
+
+fn load<T: FromEnv + Default>(
+	v: &mut Vec<T>,
+	source: &dyn EnvSource,
+	prefix: &str,
+	options: &VecOptions,
+	sink: &mut impl Sink,
+) -> Result<bool> {
+	let mut writer = Writer::new(v, options.mode);
+
+	if let Some(delimiter) = options.delimiter {
+		match source.var(prefix) {
+			Ok(value) => {
+				load_delimited(&mut writer, prefix, &value, delimiter, sink)?;
+				return Ok(writer.finish());
+			}
+			Err(VarError::NotPresent) => {}
+			Err(VarError::NotUnicode(s)) => {
+				sink.report(prefix, FromEnvError::not_unicode(prefix, s))?;
+				return Ok(false);
+			}
+		}
+	}
+
+	match options.indices {
+		Indices::Contiguous => load_indexed(&mut writer, source, prefix, sink)?,
+		Indices::Compact | Indices::Strict => {
+			let indices = scan_indices(source, prefix);
+
+			if options.indices == Indices::Strict {
+				let gap = indices
+					.iter()
+					.zip(0..)
+					.find(|(index, expected)| **index != *expected);
+				if let Some((_, expected)) = gap {
+					sink.report(prefix, FromEnvError::index_gap(prefix, expected))?;
+					return Ok(false);
+				}
+			}
+
+			for index in indices {
+				let var = format!("{prefix}{}{index}", separator());
+				writer.load(index, source, &var, sink)?;
+			}
+		}
+	}
+
+	Ok(writer.finish())
+}
+
+/// Applies elements to a vector as they are loaded, according to a [`VecMode`].
+struct Writer<'a, T> {
+	v: &'a mut Vec<T>,
+	mode: VecMode,
+	/// Elements which are not merged into an existing element.
+	new: Vec<T>,
+	/// Whether any element was found.
+	found: bool,
+}
+
+impl<'a, T: FromEnv + Default> Writer<'a, T> {
+	fn new(v: &'a mut Vec<T>, mode: VecMode) -> Self {
+		Self {
+			v,
+			mode,
+			new: Vec::new(),
+			found: false,
+		}
+	}
+
+	/// Loads the element at `index` from `var`, returning whether or not it was found.
+	///
+	/// An element with none of its variables set is absent, even if it has required fields.
+	fn load(
+		&mut self,
+		index: usize,
+		source: &dyn EnvSource,
+		var: &str,
+		sink: &mut impl Sink,
+	) -> Result<bool> {
+		let segment = PathSegment::Index(index);
+		let found = match self.v.get_mut(index) {
+			Some(existing) if self.mode == VecMode::Merge => {
+				sink.scope(segment, |sink| sink.load_present(existing, source, var))?
+			}
+			_ => {
+				let mut contents = T::default();
+				let found = sink.scope(segment, |sink| {
+					sink.load_present(&mut contents, source, var)
+				})?;
+				if found {
+					self.new.push(contents);
+				}
+				found
+			}
+		};
+
+		self.found |= found;
+		Ok(found)
+	}
+
+	/// Returns `true` if the element at `index` would be merged into an existing element.
+	///
+	/// Existing elements need not be patched, so they never end a contiguous vector.
+	fn merges(&self, index: usize) -> bool {
+		self.mode == VecMode::Merge && index < self.v.len()
+	}
+
+	/// Writes the loaded elements to the vector, returning whether any were found.
+	fn finish(self) -> bool {
+		if self.found {
+			match self.mode {
+				VecMode::Replace => *self.v = self.new,
+				VecMode::Merge | VecMode::Append => self.v.extend(self.new),
+			}
+		}
+
+		self.found
+	}
+}
+
+/// Lists every index `n` for which a variable named `PREFIX_<n>` or `PREFIX_<n>_...` exists.
+fn scan_indices(source: &dyn EnvSource, prefix: &str) -> BTreeSet<usize> {
+	source
+		.keys()
+		.iter()
+		.filter_map(|key| key.strip_prefix(prefix)?.strip_prefix(separator()))
+		.filter_map(|rest| {
+			let digits = rest.split(separator()).next()?;
+			// Leading zeros would name a different variable than the index does.
+			if digits.len() > 1 && digits.starts_with('0') {
+				return None;
+			}
+			digits.parse().ok()
+		})
+		.collect()
+}
+
+fn load_delimited<T: FromEnv + Default>(
+	writer: &mut Writer<'_, T>,
+	prefix: &str,
+	value: &str,
+	delimiter: char,
+	sink: &mut impl Sink,
+) -> Result<()> {
+	let elements = match split(value, delimiter) {
+		Ok(elements) => elements,
+		Err(msg) => {
+			return sink.report(prefix, FromEnvError::parse_error(prefix, value, msg));
+		}
+	};
+
+	// The variable was present, even if the list is empty.
+	writer.found = true;
+
+	for (index, element) in elements.iter().enumerate() {
+		// Each element is parsed as if it were the only variable present.
+		let element_source = [(prefix, element.as_str())];
+		let error_count = sink.error_count();
+
+		if !writer.load(index, &element_source, prefix, sink)? && sink.error_count() == error_count
+		{
+			let mut error = FromEnvError::parse_error(
+				prefix,
+				value,
+				format!("list element {element:?} was not used"),
+			);
+			error.within(PathSegment::Index(index));
+			sink.report(prefix, error)?;
+		}
+	}
+
+	Ok(())
+}
+
+fn load_indexed<T: FromEnv + Default>(
+	writer: &mut Writer<'_, T>,
+	source: &dyn EnvSource,
+	prefix: &str,
+	sink: &mut impl Sink,
+) -> Result<()> {
+	// Working environment variable.
+	let separator = separator();
+	let mut var = format!("{prefix}{separator}0");
+
+	// Counter as a string.
+	// This is done on the stack to avoid allocations.
+	let mut digits = DigitContainer::new();
+
+	for index in 0.. {
+		let error_count = sink.error_count();
+
+		if !writer.load(index, source, &var, sink)?
+			&& sink.error_count() == error_count
+			&& !writer.merges(index)
+		{
+			// Neither used nor erroneous; this is the end of the vector.
+			break;
+		}
+
+		// Rebuild var with no allocations.
+		// (This isn't actually realloc-free; the string may overflow if the digit value becomes too large).
+		// Truncate only modifies the "size" field, meaning we keep our allocated memory.
+		var.truncate(prefix.len() + separator.len());
+		// Then the previous digits are overwritten.
+		digits.next(&mut var);
+	}
+
+	Ok(())
+}
+
+/// Splits a delimited list, honoring `\` escapes and `"` quotes.
+fn split(value: &str, delimiter: char) -> std::result::Result<Vec<String>, &'static str> {
+	let mut elements = Vec::new();
+
+	if value.trim().is_empty() {
+		return Ok(elements);
+	}
+
+	let mut element = String::new();
+	// Length of `element` up to its last character which should not be trimmed.
+	let mut kept = 0;
+	let mut chars = value.chars();
+
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => {
+				element.push(chars.next().ok_or("trailing backslash")?);
+				kept = element.len();
+			}
+			'"' => {
+				loop {
+					match chars.next() {
+						Some('"') => break,
+						Some('\\') => element.push(chars.next().ok_or("trailing backslash")?),
+						Some(c) => element.push(c),
+						None => return Err("unterminated quote"),
+					}
+				}
+				kept = element.len();
+			}
+			c if c == delimiter => {
+				element.truncate(kept);
+				elements.push(std::mem::take(&mut element));
+				kept = 0;
+			}
+			c if c.is_whitespace() && element.is_empty() => {}
+			c => {
+				element.push(c);
+				if !c.is_whitespace() {
+					kept = element.len();
+				}
+			}
+		}
+	}
+
+	element.truncate(kept);
+	elements.push(element);
+
+	Ok(elements)
+}
+
+/// Helper type for mainting a no-alloc string representation.
+#[derive(Debug)]
+struct DigitContainer {
+	// This could be improved slightly by using `ascii::Char` with `from_u8_unchecked`,
+	// but at the time of writing this requires nightly (and the obvious `unsafe` block).
+	digits: [u8; usize::MAX.ilog10() as usize],
+}
+
+impl DigitContainer {
+	fn new() -> Self {
+		let mut digits: [u8; usize::MAX.ilog10() as usize] = [0; usize::MAX.ilog10() as usize];
+		digits[0] = 1;
+		Self { digits }
+	}
+
+	fn next(&mut self, s: &mut String) {
+		let mut digit_iter = self.digits.into_iter().rev();
+
+		while let Some(digit) = digit_iter.next() {
+			if digit != 0 {
+				s.push((digit + b'0').into());
+
+				for digit in digit_iter {
+					s.push((digit + b'0').into());
+				}
+				break;
+			}
+		}
+
+		for digit in &mut self.digits {
+			*digit += 1;
+			if *digit != 10 {
+				break;
+			} else {
+				*digit = 0
+			}
+		}
+	}
+}
@@ -0,0 +1,74 @@
+//! Implementations forwarding to the value inside smart pointers and cells.
+
+use crate::{EnvSource, FromEnv, FromEnvErrors, Result};
+use std::{
+	cell::{Cell, RefCell},
+	rc::Rc,
+	sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+/// Implements [`FromEnv`] for `$ty` by loading the `&mut T` which `$inner` evaluates to.
+macro_rules! forward {
+	($(
+		$(#[$attr:meta])*
+		$ty:ty $(where T: $bound:path)? => |$this:ident| $inner:expr;
+	)+) => {$(
+		$(#[$attr])*
+		impl<T: FromEnv $(+ $bound)?> FromEnv for $ty {
+			const PREFIX: &'static str = T::PREFIX;
+
+			fn with_env_from(&mut self, source: &dyn EnvSource, var: &str) -> Result<bool> {
+				let $this = self;
+				$inner.with_env_from(source, var)
+			}
+
+			fn collect_env_from(
+				&mut self,
+				source: &dyn EnvSource,
+				var: &str,
+				errors: &mut FromEnvErrors,
+			) -> bool {
+				let $this = self;
+				$inner.collect_env_from(source, var, errors)
+			}
+		}
+	)+};
+}
+
+forward! {
+	Box<T> => |this| this.as_mut();
+	/// Loads the value in place if this is the only reference to it,
+	/// and otherwise clones it first (see [`Rc::make_mut`]).
+	Rc<T> where T: Clone => |this| Rc::make_mut(this);
+	/// Loads the value in place if this is the only reference to it,
+	/// and otherwise clones it first (see [`Arc::make_mut`]).
+	///
+	/// ```rust
+	/// use derive_environment::FromEnv;
+	/// use std::sync::Arc;
+	///
+	/// #[derive(Clone, Default, FromEnv)]
+	/// struct Database {
+	///     port: u16,
+	/// }
+	///
+	/// #[derive(Default, FromEnv)]
+	/// struct Config {
+	///     database: Arc<Database>,
+	/// }
+	///
+	/// let mut config = Config::default();
+	/// let shared = Arc::clone(&config.database);
+	///
+	/// config.with_env_from(&[("APP_DATABASE_PORT", "5432")], "APP").unwrap();
+	/// assert_eq!(config.database.port, 5432);
+	/// assert_eq!(shared.port, 0);
+	/// ```
+	Arc<T> where T: Clone => |this| Arc::make_mut(this);
+	Cell<T> => |this| this.get_mut();
+	RefCell<T> => |this| this.get_mut();
+	/// A poisoned mutex is loaded anyway, since the value is about to be overwritten.
+	Mutex<T> => |this| this.get_mut().unwrap_or_else(PoisonError::into_inner);
+	/// A poisoned lock is loaded anyway, since the value is about to be overwritten.
+	RwLock<T> => |this| this.get_mut().unwrap_or_else(PoisonError::into_inner);
+}